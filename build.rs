@@ -21,6 +21,12 @@ fn recursion<P: AsRef<Path>>(v: &mut Vec<String>, dir: P) -> Result<()> {
 fn main() -> Result<()> {
     let mut v = Vec::<String>::new();
     recursion(&mut v, "idl")?;
-    prost_build::compile_protos(&v, &["idl"])?;
+    // map 使用 BTreeMap，按 key 排序编码，保证签名时重新编码的结果一致
+    let mut config = prost_build::Config::new();
+    config.btree_map(["."]);
+    tonic_build::configure()
+        .build_client(false)
+        .type_attribute(".", "#[derive(serde::Serialize)]")
+        .compile_with_config(config, &v, &["idl".to_string()])?;
     Ok(())
 }
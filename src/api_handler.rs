@@ -1,21 +1,35 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use ricq::structs::GroupMemberPermission;
 use ricq_core::structs::{MusicShare, MusicVersion};
 
+use crate::bot::contact::{gender_to_sex, permission_to_role};
 use crate::bot::Bot;
 use crate::error::{RCError, RCResult};
-use crate::idl::pbbot::frame::Data;
+use crate::idl::pbbot::frame::{Data, FrameType};
 use crate::idl::pbbot::*;
-use crate::msg::{to_rq_chain, Contact};
+use crate::msg::from_xml::xml_to_proto;
+use crate::msg::to_xml::proto_to_xml;
+use crate::msg::{
+    find_record, to_proto_chain, to_proto_forward, to_rq_chain, to_rq_forward,
+    upload_friend_record, upload_group_record, Contact,
+};
+use crate::util::audio::{decode_silk, is_amr, is_silk, pcm_to_wav};
+use crate::util::group_file::{
+    file_name_from_uri, get_group_file_url, upload_group_file, FileList, DELETE_FILE_URL,
+    FILE_LIST_URL,
+};
+use crate::util::honor::{get_group_honor_info, HONOR_URL};
+use crate::util::uri_reader::get_binary;
 
-pub async fn handle_api_frame(bot: &Arc<Bot>, req_frame: Frame) -> Frame {
+// origin 为调用方，插件名、脚本插件名或 grpc，用于 message_sent 事件
+pub async fn handle_api_frame(bot: &Arc<Bot>, req_frame: Frame, origin: &str) -> Frame {
     let bot_id = req_frame.bot_id;
     let echo = req_frame.echo;
     let frame_type = req_frame.frame_type;
     let resp = if let Some(data) = req_frame.data {
-        handle_api_data(bot, data).await
+        handle_api_data(bot, data, origin).await
     } else {
         None
     };
@@ -30,31 +44,86 @@ pub async fn handle_api_frame(bot: &Arc<Bot>, req_frame: Frame) -> Frame {
     }
 }
 
-pub async fn handle_api_data(bot: &Arc<Bot>, data: Data) -> Option<Data> {
+// 已实现的 API，需要和 call_api 保持一致
+pub const SUPPORTED_APIS: &[FrameType] = &[
+    FrameType::TSendPrivateMsgReq,
+    FrameType::TSendGroupMsgReq,
+    FrameType::TSendMsgReq,
+    FrameType::TDeleteMsgReq,
+    FrameType::TGetMsgReq,
+    FrameType::TGetForwardMsgReq,
+    FrameType::TSendLikeReq,
+    FrameType::TSetGroupKickReq,
+    FrameType::TSetGroupBanReq,
+    FrameType::TSetGroupAnonymousBanReq,
+    FrameType::TSetGroupWholeBanReq,
+    FrameType::TSetGroupAdminReq,
+    FrameType::TSetGroupAnonymousReq,
+    FrameType::TSetGroupCardReq,
+    FrameType::TSetGroupNameReq,
+    FrameType::TSetGroupLeaveReq,
+    FrameType::TSetGroupSpecialTitleReq,
+    FrameType::TSetFriendAddRequestReq,
+    FrameType::TSetGroupAddRequestReq,
+    FrameType::TGetLoginInfoReq,
+    FrameType::TGetStrangerInfoReq,
+    FrameType::TGetFriendListReq,
+    FrameType::TGetGroupInfoReq,
+    FrameType::TGetGroupListReq,
+    FrameType::TGetGroupMemberInfoReq,
+    FrameType::TGetGroupMemberListReq,
+    FrameType::TGetGroupHonorInfoReq,
+    FrameType::TGetCookiesReq,
+    FrameType::TGetCsrfTokenReq,
+    FrameType::TGetCredentialsReq,
+    FrameType::TGetRecordReq,
+    FrameType::TCanSendRecordReq,
+    FrameType::TSetGroupSignInReq,
+    FrameType::TSendMusicReq,
+    FrameType::TSendPokeReq,
+    FrameType::TUploadGroupFileReq,
+    FrameType::TGetGroupFileUrlReq,
+    FrameType::TGetGroupFilesReq,
+    FrameType::TDeleteGroupFileReq,
+];
+
+pub async fn handle_api_data(bot: &Arc<Bot>, data: Data, origin: &str) -> Option<Data> {
+    call_api(bot, data, origin).await.ok()
+}
+
+pub async fn call_api(bot: &Arc<Bot>, data: Data, origin: &str) -> RCResult<Data> {
     match data {
-        Data::SendPrivateMsgReq(req) => handle_send_private_msg(bot, req)
+        Data::SendPrivateMsgReq(req) => handle_send_private_msg(bot, req, origin)
             .await
             .map(Data::SendPrivateMsgResp),
-        Data::SendGroupMsgReq(req) => handle_send_group_msg(bot, req)
+        Data::SendGroupMsgReq(req) => handle_send_group_msg(bot, req, origin)
             .await
             .map(Data::SendGroupMsgResp),
-        // Data::SendMsgReq(_) => {}
+        Data::SendMsgReq(req) => handle_send_msg(bot, req, origin)
+            .await
+            .map(Data::SendMsgResp),
         Data::DeleteMsgReq(req) => handle_delete_msg(bot, req).await.map(Data::DeleteMsgResp),
-        // Data::GetMsgReq(_) => {}
-        // Data::GetForwardMsgReq(_) => {}
+        Data::GetMsgReq(req) => handle_get_msg(bot, req).await.map(Data::GetMsgResp),
+        Data::GetForwardMsgReq(req) => handle_get_forward_msg(bot, req)
+            .await
+            .map(Data::GetForwardMsgResp),
         Data::SendLikeReq(req) => handle_send_like(bot, req).await.map(Data::SendLikeResp),
         Data::SetGroupKickReq(req) => handle_group_kick(bot, req)
             .await
             .map(Data::SetGroupKickResp),
         Data::SetGroupBanReq(req) => handle_group_ban(bot, req).await.map(Data::SetGroupBanResp),
-        // Data::SetGroupAnonymousBanReq(_) => {}
+        Data::SetGroupAnonymousBanReq(req) => handle_group_anonymous_ban(bot, req)
+            .await
+            .map(Data::SetGroupAnonymousBanResp),
         Data::SetGroupWholeBanReq(req) => handle_group_whole_ban(bot, req)
             .await
             .map(Data::SetGroupWholeBanResp),
         Data::SetGroupAdminReq(req) => handle_set_group_admin(bot, req)
             .await
             .map(Data::SetGroupAdminResp),
-        // Data::SetGroupAnonymousReq(_) => {}
+        Data::SetGroupAnonymousReq(req) => handle_set_group_anonymous(bot, req)
+            .await
+            .map(Data::SetGroupAnonymousResp),
         Data::SetGroupCardReq(req) => handle_set_group_card(bot, req)
             .await
             .map(Data::SetGroupCardResp),
@@ -94,14 +163,22 @@ pub async fn handle_api_data(bot: &Arc<Bot>, data: Data) -> Option<Data> {
         Data::GetGroupMemberListReq(req) => handle_get_group_member_list(bot, req)
             .await
             .map(Data::GetGroupMemberListResp),
-        // Data::GetGroupHonorInfoReq(_) => {}
-        // Data::GetCookiesReq(_) => {}
-        // Data::GetCsrfTokenReq(_) => {}
-        // Data::GetCredentialsReq(_) => {}
-        // Data::GetRecordReq(_) => {}
+        Data::GetGroupHonorInfoReq(req) => handle_get_group_honor_info(bot, req)
+            .await
+            .map(Data::GetGroupHonorInfoResp),
+        Data::GetCookiesReq(req) => handle_get_cookies(bot, req).await.map(Data::GetCookiesResp),
+        Data::GetCsrfTokenReq(req) => handle_get_csrf_token(bot, req)
+            .await
+            .map(Data::GetCsrfTokenResp),
+        Data::GetCredentialsReq(req) => handle_get_credentials(bot, req)
+            .await
+            .map(Data::GetCredentialsResp),
+        Data::GetRecordReq(req) => handle_get_record(bot, req).await.map(Data::GetRecordResp),
         // Data::GetImageReq(_) => {}
         // Data::CanSendImageReq(_) => {}
-        // Data::CanSendRecordReq(_) => {}
+        Data::CanSendRecordReq(req) => handle_can_send_record(bot, req)
+            .await
+            .map(Data::CanSendRecordResp),
         // Data::GetStatusReq(_) => {}
         // Data::GetVersionInfoReq(_) => {}
         // Data::SetRestartReq(_) => {}
@@ -110,26 +187,64 @@ pub async fn handle_api_data(bot: &Arc<Bot>, data: Data) -> Option<Data> {
             .await
             .map(Data::SetGroupSignInResp),
         Data::SendMusicReq(req) => handle_send_music(bot, req).await.map(Data::SendMusicResp),
-        _ => Err(RCError::None("api_req not supported")),
+        Data::SendPokeReq(req) => handle_send_poke(bot, req).await.map(Data::SendPokeResp),
+        Data::UploadGroupFileReq(req) => handle_upload_group_file(bot, req)
+            .await
+            .map(Data::UploadGroupFileResp),
+        Data::GetGroupFileUrlReq(req) => handle_get_group_file_url(bot, req)
+            .await
+            .map(Data::GetGroupFileUrlResp),
+        Data::GetGroupFilesReq(req) => handle_get_group_files(bot, req)
+            .await
+            .map(Data::GetGroupFilesResp),
+        Data::DeleteGroupFileReq(req) => handle_delete_group_file(bot, req)
+            .await
+            .map(Data::DeleteGroupFileResp),
+        _ => Err(RCError::Unsupported),
     }
-    .ok()
 }
 
 pub async fn handle_send_private_msg(
     bot: &Arc<Bot>,
     req: SendPrivateMsgReq,
+    origin: &str,
 ) -> RCResult<SendPrivateMsgResp> {
-    let chain = to_rq_chain(
-        &bot.client,
-        req.message,
-        Contact::Friend(req.user_id),
-        req.auto_escape,
-    )
-    .await;
-    let receipt = bot
-        .client
-        .send_friend_message(req.user_id, chain.clone())
+    // group_id 为 0 时，好友直接发送，否则使用最近的临时会话
+    let group_id = if req.group_id != 0 {
+        req.group_id
+    } else if bot.contacts.friend(req.user_id).is_some() {
+        0
+    } else {
+        bot.temp_session(req.user_id).unwrap_or_default()
+    };
+    if group_id != 0 {
+        let message_id = handle_send_temp_msg(
+            bot,
+            group_id,
+            req.user_id,
+            req.message,
+            req.auto_escape,
+            origin,
+        )
         .await?;
+        return Ok(SendPrivateMsgResp {
+            message_id: Some(message_id),
+        });
+    }
+    let (receipt, message) = if let Some(record) = find_record(&req.message) {
+        // 包含 record 时只发送语音
+        let audio = upload_friend_record(&bot.client, req.user_id, record.clone()).await?;
+        let receipt = bot.client.send_friend_audio(req.user_id, audio).await?;
+        (receipt, record_message(record))
+    } else {
+        let message = fill_reply(bot, req.message, req.auto_escape, 0, req.user_id).await;
+        let chain = to_rq_chain(&bot.client, message, Contact::Friend(req.user_id), true).await;
+        let receipt = bot
+            .client
+            .send_friend_message(req.user_id, chain.clone())
+            .await?;
+        (receipt, to_proto_chain(&bot.client, chain))
+    };
     let message_id = MessageReceipt {
         sender_id: bot.client.uin().await,
         time: receipt.time,
@@ -137,6 +252,7 @@ pub async fn handle_send_private_msg(
         rands: receipt.rands,
         group_id: 0,
     };
+    report_sent_msg(bot, &message_id, req.user_id, message, origin).await;
     Ok(SendPrivateMsgResp {
         message_id: Some(message_id),
     })
@@ -145,18 +261,31 @@ pub async fn handle_send_private_msg(
 pub async fn handle_send_group_msg(
     bot: &Arc<Bot>,
     req: SendGroupMsgReq,
+    origin: &str,
 ) -> RCResult<SendGroupMsgResp> {
-    let chain = to_rq_chain(
-        &bot.client,
-        req.message,
-        Contact::Group(req.group_id),
-        req.auto_escape,
-    )
-    .await;
-    let receipt = bot
-        .client
-        .send_group_message(req.group_id, chain.clone())
-        .await?;
+    let (receipt, message) = if req.message.iter().any(|m| m.r#type == "node") {
+        // 包含 node 时发送合并转发
+        let message = req.message.clone();
+        let nodes = to_rq_forward(&bot.client, req.message, Contact::Group(req.group_id)).await;
+        let receipt = bot
+            .client
+            .send_group_forward_message(req.group_id, nodes)
+            .await?;
+        (receipt, message)
+    } else if let Some(record) = find_record(&req.message) {
+        // 包含 record 时只发送语音
+        let audio = upload_group_record(&bot.client, req.group_id, record.clone()).await?;
+        let receipt = bot.client.send_group_audio(req.group_id, audio).await?;
+        (receipt, record_message(record))
+    } else {
+        let message = fill_reply(bot, req.message, req.auto_escape, req.group_id, 0).await;
+        let chain = to_rq_chain(&bot.client, message, Contact::Group(req.group_id), true).await;
+        let receipt = bot
+            .client
+            .send_group_message(req.group_id, chain.clone())
+            .await?;
+        (receipt, to_proto_chain(&bot.client, chain))
+    };
     let message_id = MessageReceipt {
         sender_id: bot.client.uin().await,
         time: receipt.time,
@@ -164,11 +293,276 @@ pub async fn handle_send_group_msg(
         rands: receipt.rands,
         group_id: req.group_id,
     };
+    report_sent_msg(bot, &message_id, req.group_id, message, origin).await;
     Ok(SendGroupMsgResp {
         message_id: Some(message_id),
     })
 }
 
+pub async fn handle_send_temp_msg(
+    bot: &Arc<Bot>,
+    group_id: i64,
+    user_id: i64,
+    message: Vec<Message>,
+    auto_escape: bool,
+    origin: &str,
+) -> RCResult<MessageReceipt> {
+    let message = fill_reply(bot, message, auto_escape, 0, user_id).await;
+    // 不是好友，图片和视频按来源群上传
+    let chain = to_rq_chain(&bot.client, message, Contact::Group(group_id), true).await;
+    let receipt = bot
+        .client
+        .send_group_temp_message(group_id, user_id, chain.clone())
+        .await?;
+    let message_id = MessageReceipt {
+        sender_id: bot.client.uin().await,
+        time: receipt.time,
+        seqs: receipt.seqs,
+        rands: receipt.rands,
+        group_id: 0,
+    };
+    let message = to_proto_chain(&bot.client, chain);
+    report_sent_msg(bot, &message_id, user_id, message, origin).await;
+    Ok(message_id)
+}
+
+// 展开 xml 后，reply 缺少 user_id、time、text 时从消息记录补全，返回的消息不需要再转义
+// 私聊 group_id 为 0，user_id 为对方
+async fn fill_reply(
+    bot: &Arc<Bot>,
+    message: Vec<Message>,
+    auto_escape: bool,
+    group_id: i64,
+    user_id: i64,
+) -> Vec<Message> {
+    let mut message: Vec<Message> = if auto_escape {
+        message
+    } else {
+        message
+            .into_iter()
+            .flat_map(|m| match m.r#type.as_str() {
+                "text" => xml_to_proto(m.data.get("text").cloned().unwrap_or_default()),
+                _ => vec![m],
+            })
+            .collect()
+    };
+    for element in message.iter_mut().filter(|m| m.r#type == "reply") {
+        let data = &mut element.data;
+        if ["user_id", "time", "text"]
+            .iter()
+            .all(|k| data.contains_key(*k))
+        {
+            continue;
+        }
+        let field = |k: &str| data.get(k).and_then(|v| v.parse().ok()).unwrap_or_default();
+        let receipt = MessageReceipt {
+            sender_id: field("user_id"),
+            time: field("time"),
+            seqs: vec![field("seq") as i32],
+            rands: vec![],
+            group_id,
+        };
+        let record = if group_id != 0 || (receipt.sender_id != 0 && receipt.time != 0) {
+            bot.msg_store.get(&receipt).await
+        } else {
+            find_private_reply(bot, &receipt, user_id).await
+        };
+        let record = match record {
+            Some(record) => record,
+            None => {
+                tracing::warn!("replied message not found {:?}", receipt);
+                continue;
+            }
+        };
+        let text: String = record
+            .message
+            .iter()
+            .filter(|m| m.r#type == "text")
+            .filter_map(|m| m.data.get("text").cloned())
+            .collect();
+        data.entry("user_id".into()).or_insert_with(|| {
+            record
+                .sender
+                .map(|s| s.user_id)
+                .unwrap_or_default()
+                .to_string()
+        });
+        data.entry("time".into())
+            .or_insert_with(|| record.time.to_string());
+        data.entry("text".into()).or_insert(text);
+    }
+    message
+}
+
+// 私聊 key 包含发送者和时间，缺少时按 seq 查找对方或自己发送的消息
+async fn find_private_reply(
+    bot: &Arc<Bot>,
+    receipt: &MessageReceipt,
+    user_id: i64,
+) -> Option<GetMsgResp> {
+    let seq = receipt.seqs.first().cloned().unwrap_or_default();
+    let senders = if receipt.sender_id != 0 {
+        vec![receipt.sender_id]
+    } else {
+        vec![user_id, bot.client.uin().await]
+    };
+    for sender_id in senders {
+        if let Some(record) = bot.msg_store.get_private(sender_id, seq).await {
+            return Some(record);
+        }
+    }
+    None
+}
+
+fn record_message(data: BTreeMap<String, String>) -> Vec<Message> {
+    vec![Message {
+        r#type: "record".into(),
+        data,
+    }]
+}
+
+// 推送 message_sent 事件，dispatch_event 时保存，target_id 为群号或私聊对象
+// 服务器推送的同一条消息先到时已经推送过，不再重复
+async fn report_sent_msg(
+    bot: &Arc<Bot>,
+    message_id: &MessageReceipt,
+    target_id: i64,
+    message: Vec<Message>,
+    origin: &str,
+) {
+    let message_type = if message_id.group_id != 0 {
+        "group"
+    } else {
+        "private"
+    };
+    if bot.msg_store.mark_sent(message_id).await {
+        return;
+    }
+    let record = sent_msg_record(bot, message_type, message_id, message).await;
+    let extra = BTreeMap::from([
+        ("origin".to_string(), origin.to_string()),
+        ("target_id".to_string(), target_id.to_string()),
+    ]);
+    let sender = record.sender.unwrap_or_default();
+    let event = if message_id.group_id != 0 {
+        Data::GroupMessageEvent(GroupMessageEvent {
+            time: message_id.time,
+            self_id: message_id.sender_id,
+            post_type: "message_sent".into(),
+            message_type: "group".into(),
+            sub_type: "normal".into(),
+            message_id: Some(message_id.clone()),
+            group_id: message_id.group_id,
+            user_id: message_id.sender_id,
+            anonymous: None,
+            raw_message: record.raw_message,
+            message: record.message,
+            sender: Some(group_message_event::Sender {
+                user_id: sender.user_id,
+                nickname: sender.nickname,
+                ..Default::default()
+            }),
+            font: 0,
+            extra,
+        })
+    } else {
+        Data::PrivateMessageEvent(PrivateMessageEvent {
+            time: message_id.time,
+            self_id: message_id.sender_id,
+            post_type: "message_sent".into(),
+            message_type: "private".into(),
+            sub_type: "normal".into(),
+            message_id: Some(message_id.clone()),
+            user_id: message_id.sender_id,
+            raw_message: record.raw_message,
+            message: record.message,
+            sender: Some(private_message_event::Sender {
+                user_id: sender.user_id,
+                nickname: sender.nickname,
+                ..Default::default()
+            }),
+            font: 0,
+            extra,
+        })
+    };
+    bot.dispatch_event(event).await;
+}
+
+async fn sent_msg_record(
+    bot: &Arc<Bot>,
+    message_type: &str,
+    message_id: &MessageReceipt,
+    message: Vec<Message>,
+) -> GetMsgResp {
+    GetMsgResp {
+        time: message_id.time as i32,
+        message_type: message_type.into(),
+        real_id: message_id.seqs.first().cloned().unwrap_or_default(),
+        sender: Some(get_msg_resp::Sender {
+            user_id: message_id.sender_id,
+            nickname: bot.client.account_info.read().await.nickname.clone(),
+            ..Default::default()
+        }),
+        raw_message: proto_to_xml(message.clone()),
+        message,
+        message_id: Some(message_id.clone()),
+        group_id: message_id.group_id,
+    }
+}
+
+// message_type 为空时，有 group_id 发送群消息，否则发送私聊消息；私聊带 group_id 时发送临时会话
+pub async fn handle_send_msg(
+    bot: &Arc<Bot>,
+    req: SendMsgReq,
+    origin: &str,
+) -> RCResult<SendMsgResp> {
+    let message_type = if req.message_type.is_empty() {
+        if req.group_id != 0 {
+            "group"
+        } else {
+            "private"
+        }
+    } else {
+        req.message_type.as_str()
+    };
+    let message_id = match message_type {
+        "group" => {
+            handle_send_group_msg(
+                bot,
+                SendGroupMsgReq {
+                    group_id: req.group_id,
+                    message: req.message,
+                    auto_escape: req.auto_escape,
+                },
+                origin,
+            )
+            .await?
+            .message_id
+        }
+        "private" => {
+            handle_send_private_msg(
+                bot,
+                SendPrivateMsgReq {
+                    user_id: req.user_id,
+                    message: req.message,
+                    auto_escape: req.auto_escape,
+                    group_id: req.group_id,
+                },
+                origin,
+            )
+            .await?
+            .message_id
+        }
+        _ => {
+            return Err(RCError::Other(format!(
+                "invalid message_type {}",
+                message_type
+            )))
+        }
+    };
+    Ok(SendMsgResp { message_id })
+}
+
 pub async fn handle_delete_msg(bot: &Arc<Bot>, req: DeleteMsgReq) -> RCResult<DeleteMsgResp> {
     let receipt = req.message_id.ok_or(RCError::None("message_id"))?;
     if receipt.group_id != 0 {
@@ -183,6 +577,24 @@ pub async fn handle_delete_msg(bot: &Arc<Bot>, req: DeleteMsgReq) -> RCResult<De
     Ok(DeleteMsgResp {})
 }
 
+pub async fn handle_get_msg(bot: &Arc<Bot>, req: GetMsgReq) -> RCResult<GetMsgResp> {
+    let receipt = req.message_id.ok_or(RCError::None("message_id"))?;
+    bot.msg_store
+        .get(&receipt)
+        .await
+        .ok_or(RCError::NotFound("message"))
+}
+
+pub async fn handle_get_forward_msg(
+    bot: &Arc<Bot>,
+    req: GetForwardMsgReq,
+) -> RCResult<GetForwardMsgResp> {
+    let nodes = bot.client.download_msgs(req.id).await?;
+    Ok(GetForwardMsgResp {
+        message: Some(to_proto_forward(&bot.client, nodes)),
+    })
+}
+
 pub async fn handle_send_like(bot: &Arc<Bot>, req: SendLikeReq) -> RCResult<SendLikeResp> {
     let summary = bot.client.get_summary_info(req.user_id).await?;
     bot.client
@@ -211,6 +623,50 @@ pub async fn handle_group_ban(bot: &Arc<Bot>, req: SetGroupBanReq) -> RCResult<S
     Ok(SetGroupBanResp {})
 }
 
+// flag 为 GroupMessageEvent.anonymous.flag
+pub async fn handle_group_anonymous_ban(
+    bot: &Arc<Bot>,
+    req: SetGroupAnonymousBanReq,
+) -> RCResult<SetGroupAnonymousBanResp> {
+    let flag = [
+        req.anonymous.map(|a| a.flag).unwrap_or_default(),
+        req.anonymous_flag,
+        req.flag,
+    ]
+    .into_iter()
+    .find(|f| !f.is_empty())
+    .ok_or(RCError::None("anonymous_flag"))?;
+    let (id, nick) = flag
+        .split_once('|')
+        .ok_or_else(|| RCError::Other("invalid anonymous_flag".into()))?;
+    bot.web_post(
+        "https://qqweb.qq.com/c/anonymoustalk/blacklist",
+        &[
+            ("anony_id", id.to_string()),
+            ("group_code", req.group_id.to_string()),
+            ("seconds", req.duration.to_string()),
+            ("anony_nick", nick.to_string()),
+        ],
+    )
+    .await?;
+    Ok(SetGroupAnonymousBanResp {})
+}
+
+pub async fn handle_set_group_anonymous(
+    bot: &Arc<Bot>,
+    req: SetGroupAnonymousReq,
+) -> RCResult<SetGroupAnonymousResp> {
+    bot.web_post(
+        "https://qqweb.qq.com/c/anonymoustalk/set_anony_switch",
+        &[
+            ("group_code", req.group_id.to_string()),
+            ("value", (req.enable as i32).to_string()),
+        ],
+    )
+    .await?;
+    Ok(SetGroupAnonymousResp {})
+}
+
 pub async fn handle_group_whole_ban(
     bot: &Arc<Bot>,
     req: SetGroupWholeBanReq,
@@ -320,7 +776,7 @@ pub async fn handle_get_group_info(
         .client
         .get_group_info(req.group_id)
         .await?
-        .ok_or(RCError::None("group"))?;
+        .ok_or(RCError::NotFound("group"))?;
     Ok(GetGroupInfoResp {
         group_id: group.code,
         group_name: group.name,
@@ -353,27 +809,27 @@ pub async fn handle_get_group_member_info(
     bot: &Arc<Bot>,
     req: GetGroupMemberInfoReq,
 ) -> RCResult<GetGroupMemberInfoResp> {
-    let member = bot
-        .client
-        .get_group_member_info(req.group_id, req.user_id)
-        .await?;
+    let member = if req.no_cache {
+        bot.contacts
+            .reload_member(&bot.client, req.group_id, req.user_id)
+            .await?
+    } else {
+        bot.contacts
+            .group_member(&bot.client, req.group_id, req.user_id)
+            .await?
+    };
     Ok(GetGroupMemberInfoResp {
         group_id: member.group_code,
         user_id: member.uin,
         nickname: member.nickname,
         card: member.card_name,
-        sex: "".into(), // TODO
+        sex: gender_to_sex(member.gender),
         age: 0,
         area: "".into(),
         join_time: member.join_time,
         last_sent_time: member.last_speak_time,
         level: member.level.to_string(),
-        role: match member.permission {
-            GroupMemberPermission::Owner => "owner",
-            GroupMemberPermission::Administrator => "admin",
-            GroupMemberPermission::Member => "member",
-        }
-        .to_string(),
+        role: permission_to_role(&member.permission),
         unfriendly: false,
         title: member.special_title,
         title_expire_time: member.special_title_expire_time,
@@ -385,15 +841,13 @@ pub async fn handle_get_group_member_list(
     bot: &Arc<Bot>,
     req: GetGroupMemberListReq,
 ) -> RCResult<GetGroupMemberListResp> {
-    let group = bot
-        .client
-        .get_group_info(req.group_id)
-        .await?
-        .ok_or(RCError::None("group"))?;
-    let members = bot
-        .client
-        .get_group_member_list(req.group_id, group.owner_uin)
-        .await?;
+    let members = if req.no_cache {
+        bot.contacts.reload_group(&bot.client, req.group_id).await?
+    } else {
+        bot.contacts
+            .group_members(&bot.client, req.group_id)
+            .await?
+    };
     Ok(GetGroupMemberListResp {
         group_member: members
             .into_iter()
@@ -402,18 +856,13 @@ pub async fn handle_get_group_member_list(
                 user_id: member.uin,
                 nickname: member.nickname,
                 card: member.card_name,
-                sex: "".into(), // TODO
+                sex: gender_to_sex(member.gender),
                 age: 0,
                 area: "".into(),
                 join_time: member.join_time,
                 last_sent_time: member.last_speak_time,
                 level: member.level.to_string(),
-                role: match member.permission {
-                    GroupMemberPermission::Owner => "owner",
-                    GroupMemberPermission::Administrator => "admin",
-                    GroupMemberPermission::Member => "member",
-                }
-                .to_string(),
+                role: permission_to_role(&member.permission),
                 unfriendly: false,
                 title: member.special_title,
                 title_expire_time: member.special_title_expire_time,
@@ -423,6 +872,91 @@ pub async fn handle_get_group_member_list(
     })
 }
 
+pub async fn handle_get_group_honor_info(
+    bot: &Arc<Bot>,
+    req: GetGroupHonorInfoReq,
+) -> RCResult<GetGroupHonorInfoResp> {
+    get_group_honor_info(
+        HONOR_URL,
+        &bot.cookies("qun.qq.com").await,
+        req.group_id,
+        &req.r#type,
+    )
+    .await
+}
+
+// domain 对应的 p_skey 不存在时只包含 skey
+pub async fn handle_get_cookies(bot: &Arc<Bot>, req: GetCookiesReq) -> RCResult<GetCookiesResp> {
+    Ok(GetCookiesResp {
+        cookies: bot.cookies(&req.domain).await,
+    })
+}
+
+pub async fn handle_get_csrf_token(
+    bot: &Arc<Bot>,
+    _: GetCsrfTokenReq,
+) -> RCResult<GetCsrfTokenResp> {
+    Ok(GetCsrfTokenResp {
+        token: bot.csrf_token().await,
+    })
+}
+
+pub async fn handle_get_credentials(
+    bot: &Arc<Bot>,
+    req: GetCredentialsReq,
+) -> RCResult<GetCredentialsResp> {
+    Ok(GetCredentialsResp {
+        cookies: bot.cookies(&req.domain).await,
+        csrf_token: bot.csrf_token().await,
+    })
+}
+
+// file 为 record 的 url，silk 转换为 out_format 后保存在 record 目录，返回文件路径
+pub async fn handle_get_record(_: &Arc<Bot>, req: GetRecordReq) -> RCResult<GetRecordResp> {
+    let out_format = if req.out_format.is_empty() {
+        "wav"
+    } else {
+        req.out_format.as_str()
+    };
+    let path = format!(
+        "record/{}.{}",
+        hex::encode(md5::compute(&req.file).0),
+        out_format
+    );
+    if tokio::fs::metadata(&path).await.is_ok() {
+        return Ok(GetRecordResp { file: path });
+    }
+    let data = get_binary(&req.file).await?;
+    let data = match out_format {
+        "silk" if is_silk(&data) => data,
+        "amr" if is_amr(&data) => data,
+        "wav" | "pcm" if is_silk(&data) => {
+            let wav = out_format == "wav";
+            tokio::task::spawn_blocking(move || {
+                decode_silk(&data).map(|pcm| if wav { pcm_to_wav(&pcm) } else { pcm })
+            })
+            .await
+            .map_err(|e| RCError::Other(e.to_string()))??
+        }
+        _ => {
+            return Err(RCError::Other(format!(
+                "out_format {} not supported",
+                out_format
+            )))
+        }
+    };
+    tokio::fs::create_dir_all("record").await?;
+    tokio::fs::write(&path, data).await?;
+    Ok(GetRecordResp { file: path })
+}
+
+pub async fn handle_can_send_record(
+    _: &Arc<Bot>,
+    _: CanSendRecordReq,
+) -> RCResult<CanSendRecordResp> {
+    Ok(CanSendRecordResp { yes: true })
+}
+
 pub async fn handle_set_group_add_request(
     bot: &Arc<Bot>,
     req: SetGroupAddRequestReq,
@@ -513,3 +1047,104 @@ pub async fn handle_send_music(bot: &Arc<Bot>, req: SendMusicReq) -> RCResult<Se
     }
     Ok(SendMusicResp {})
 }
+
+pub async fn handle_send_poke(bot: &Arc<Bot>, req: SendPokeReq) -> RCResult<SendPokeResp> {
+    if req.group_id != 0 {
+        bot.client.group_poke(req.group_id, req.user_id).await?;
+    } else {
+        bot.client.friend_poke(req.user_id).await?;
+    }
+    Ok(SendPokeResp {})
+}
+
+pub async fn handle_upload_group_file(
+    bot: &Arc<Bot>,
+    req: UploadGroupFileReq,
+) -> RCResult<UploadGroupFileResp> {
+    let name = if req.name.is_empty() {
+        file_name_from_uri(&req.file).ok_or(RCError::None("name"))?
+    } else {
+        req.name
+    };
+    let folder_id = if req.folder_id.is_empty() {
+        "/".into()
+    } else {
+        req.folder_id
+    };
+    let data = get_binary(&req.file).await?;
+    let (file_id, busid) = upload_group_file(bot, req.group_id, &folder_id, &name, data).await?;
+    Ok(UploadGroupFileResp { file_id, busid })
+}
+
+pub async fn handle_get_group_file_url(
+    bot: &Arc<Bot>,
+    req: GetGroupFileUrlReq,
+) -> RCResult<GetGroupFileUrlResp> {
+    Ok(GetGroupFileUrlResp {
+        url: get_group_file_url(bot, req.group_id, &req.file_id, req.busid).await?,
+    })
+}
+
+pub async fn handle_get_group_files(
+    bot: &Arc<Bot>,
+    req: GetGroupFilesReq,
+) -> RCResult<GetGroupFilesResp> {
+    let folder_id = if req.folder_id.is_empty() {
+        "/".into()
+    } else {
+        req.folder_id
+    };
+    let mut files = Vec::new();
+    let mut folders = Vec::new();
+    // 每页最多 100 个
+    loop {
+        let resp = bot
+            .web_get(
+                FILE_LIST_URL,
+                &[
+                    ("gc", req.group_id.to_string()),
+                    ("start_index", (files.len() + folders.len()).to_string()),
+                    ("cnt", "100".into()),
+                    ("filter_code", "0".into()),
+                    ("folder_id", folder_id.clone()),
+                    ("show_onlinedoc_folder", "0".into()),
+                ],
+            )
+            .await?;
+        let list: FileList = serde_json::from_value(resp)
+            .map_err(|e| RCError::Other(format!("invalid group file list: {}", e)))?;
+        let count = list.file_list.len();
+        let (page_files, page_folders) = list.into_resp();
+        files.extend(page_files);
+        folders.extend(page_folders);
+        if count < 100 {
+            break;
+        }
+    }
+    Ok(GetGroupFilesResp { files, folders })
+}
+
+pub async fn handle_delete_group_file(
+    bot: &Arc<Bot>,
+    req: DeleteGroupFileReq,
+) -> RCResult<DeleteGroupFileResp> {
+    bot.web_post(
+        DELETE_FILE_URL,
+        &[
+            ("gc", req.group_id.to_string()),
+            ("bus_id", req.busid.to_string()),
+            ("file_id", req.file_id),
+            (
+                "parent_folder_id",
+                if req.folder_id.is_empty() {
+                    "/".into()
+                } else {
+                    req.folder_id
+                },
+            ),
+            ("app_id", "4".into()),
+        ],
+    )
+    .await?;
+    Ok(DeleteGroupFileResp {})
+}
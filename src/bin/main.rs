@@ -2,6 +2,7 @@
 
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::time::Duration;
 
 use axum::{
     routing::{get, get_service, post},
@@ -13,7 +14,11 @@ use tracing::Level;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use pbrq::handler::{bot, password, plugins, qrcode};
+use pbrq::bot::bots::shutdown;
+use pbrq::bot::msg_store::init_msg_store;
+use pbrq::bot::session::restore_sessions;
+use pbrq::grpc;
+use pbrq::handler::{bot, device, password, plugins, qrcode};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -38,6 +43,22 @@ struct Args {
     /// Allow cors
     #[clap(long, value_parser, default_value_t = false)]
     cors: bool,
+
+    /// Bind addr of grpc server, disabled if not set
+    #[clap(long, value_parser)]
+    grpc_addr: Option<String>,
+
+    /// Location of message store database, messages are kept in memory only if not set
+    #[clap(long, value_parser)]
+    msg_store_dir: Option<String>,
+
+    /// Retention of stored messages in seconds
+    #[clap(long, value_parser, default_value_t = 86400)]
+    msg_retention: u64,
+
+    /// Max seconds to wait for bots and plugins to stop on exit
+    #[clap(long, value_parser, default_value_t = 10)]
+    shutdown_timeout: u64,
 }
 
 #[tokio::main]
@@ -45,6 +66,8 @@ async fn main() {
     let args = Args::parse();
     let addr = SocketAddr::from_str(&args.bind_addr).expect("failed to parse arg: bind_addr");
     init_log();
+    init_msg_store(args.msg_store_dir.as_deref(), args.msg_retention)
+        .expect("failed to init message store");
     let mut app = Router::new()
         .route("/ping", get(async move || "pong"))
         .nest(
@@ -75,6 +98,13 @@ async fn main() {
                 .route("/list", get(bot::list))
                 .route("/delete", post(bot::delete)),
         )
+        .nest(
+            "/device",
+            Router::new()
+                .route("/list", get(device::list))
+                .route("/export", post(device::export))
+                .route("/import", post(device::import)),
+        )
         .nest(
             "/plugin",
             Router::new()
@@ -97,11 +127,64 @@ async fn main() {
         tracing::info!("http_allow_cors: true");
         app = app.layer(tower_http::cors::CorsLayer::permissive())
     }
+    tokio::spawn(restore_sessions());
+    // 收到退出信号后 http 和 grpc 同时停止
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        shutdown_tx.send(()).ok();
+    });
+    if let Some(grpc_addr) = args.grpc_addr.as_ref() {
+        let grpc_addr = SocketAddr::from_str(grpc_addr).expect("failed to parse arg: grpc_addr");
+        tracing::info!("grpc listening on {}", grpc_addr);
+        let mut shutdown_rx = shutdown_rx.clone();
+        tokio::spawn(async move {
+            let signal = async move {
+                shutdown_rx.changed().await.ok();
+            };
+            if let Err(err) = grpc::serve(grpc_addr, signal).await {
+                tracing::error!("grpc server error: {}", err);
+            }
+        });
+    }
     tracing::info!("listening on {}", addr);
+    let mut shutdown_rx = shutdown_rx;
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
+        .with_graceful_shutdown(async move {
+            shutdown_rx.changed().await.ok();
+        })
         .await
         .unwrap();
+    tracing::info!("shutting down");
+    if tokio::time::timeout(Duration::from_secs(args.shutdown_timeout), shutdown())
+        .await
+        .is_err()
+    {
+        tracing::warn!("shutdown timeout");
+    }
+}
+
+// SIGINT 或 SIGTERM（docker stop）
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install ctrl_c handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install sigterm handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
 
 fn init_log() {
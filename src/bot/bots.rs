@@ -3,8 +3,9 @@ use std::time::Duration;
 
 use dashmap::DashMap;
 use lazy_static::lazy_static;
-use ricq::client::DefaultConnector;
+use ricq::client::{DefaultConnector, NetworkStatus};
 use ricq::{
+    device::Device,
     ext::common::after_login,
     ext::reconnect::{auto_reconnect, Credential},
     handler::QEvent,
@@ -14,36 +15,65 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 use tokio::task::JoinHandle;
 
+use crate::bot::device::save_device_if_absent;
+use crate::bot::msg_store::flush_msg_store;
+use crate::bot::session::{delete_session, save_bot_session};
 use crate::bot::Bot;
 use crate::handler::ConvertU8;
+use crate::plugin::conn::PluginConnection;
 use crate::plugin::storage::{load_plugins, PLUGIN_PATH};
+use crate::plugin::Plugin;
 
 lazy_static! {
     static ref BOTS: DashMap<(i64, u8), Arc<Bot>> = Default::default();
+    // 所有机器人共用的插件连接
+    static ref SHARED_PLUGINS: DashMap<String, Arc<PluginConnection>> = Default::default();
 }
 
 pub async fn on_login(
     client: Arc<Client>,
     event_receiver: broadcast::Receiver<QEvent>,
+    device: Device,
     credential: Credential,
     network_join_handle: JoinHandle<()>,
 ) {
     let uin = client.uin().await;
     let protocol = client.version().await.protocol.to_u8();
     after_login(&client).await;
-    let bot = Arc::new(Bot::new(
-        client.clone(),
-        load_plugins(PLUGIN_PATH)
-            .await
-            .expect("failed to load plugins"),
-    ));
+    let (shared, plugins): (Vec<Plugin>, Vec<Plugin>) = load_plugins(PLUGIN_PATH)
+        .await
+        .expect("failed to load plugins")
+        .into_iter()
+        .partition(|p| p.shared);
+    // 已经连接的共用插件不会再发送 hello，需要通知新机器人
+    let connected = shared_plugin_connections();
+    start_shared_plugins(shared);
+    let bot = Arc::new(Bot::new(client.clone(), uin, device, plugins));
     if let Some(old) = BOTS.insert((uin, protocol), bot.clone()) {
         old.stop();
     }
+    for plugin in connected.iter() {
+        plugin.announce_bot(&bot).await;
+    }
+    // 扫码登录时账号未知，登录后保存实际使用的设备
+    if let Err(err) = save_device_if_absent(uin, &bot.device).await {
+        tracing::error!("failed to save device: {}", err);
+    }
+    if let Err(err) = save_bot_session(&bot).await {
+        tracing::error!("failed to save session: {}", err);
+    }
     bot.start_plugins();
     bot.start_handle_event(event_receiver);
+    bot.start_status_watcher();
+    bot.start_load_friends();
     tokio::spawn(async move {
         network_join_handle.await.ok();
+        // 主动停止的不再重连
+        if client.get_status() == NetworkStatus::Stop as u8 {
+            return;
+        }
+        bot.set_online(false, "network disconnected").await;
+        bot.dispatch_lifecycle("reconnecting", "").await;
         auto_reconnect(
             client,
             credential,
@@ -55,10 +85,67 @@ pub async fn on_login(
     });
 }
 
+fn start_shared_plugins(plugins: Vec<Plugin>) {
+    for plugin in plugins {
+        if SHARED_PLUGINS.contains_key(&plugin.name) {
+            continue;
+        }
+        let conn = Arc::new(PluginConnection::new(plugin));
+        SHARED_PLUGINS.insert(conn.plugin.name.clone(), conn.clone());
+        conn.spawn(None);
+    }
+}
+
+pub fn shared_plugin_connections() -> Vec<Arc<PluginConnection>> {
+    SHARED_PLUGINS.iter().map(|p| p.value().clone()).collect()
+}
+
+// 退出前保存登录状态，停止所有机器人，保存消息后等待插件连接关闭
+pub async fn shutdown() {
+    let bots: Vec<Arc<Bot>> = BOTS.iter().map(|b| b.value().clone()).collect();
+    for bot in bots.iter() {
+        if let Err(err) = save_bot_session(bot).await {
+            tracing::error!("failed to save session: {}", err);
+        }
+        bot.stop();
+    }
+    let shared = shared_plugin_connections();
+    for plugin in shared.iter() {
+        plugin.stop();
+    }
+    // 插件可能卡在连接中，先写入磁盘，避免等待超时后丢失
+    if let Err(err) = flush_msg_store().await {
+        tracing::error!("failed to flush msg store: {}", err);
+    }
+    for bot in bots.iter() {
+        for plugin in bot.plugin_connections.values() {
+            plugin.wait_stopped().await;
+        }
+    }
+    for plugin in shared.iter() {
+        plugin.wait_stopped().await;
+    }
+}
+
 pub async fn delete_bot(uin: i64, protocol: u8) {
     if let Some((_, bot)) = BOTS.remove(&(uin, protocol)) {
         bot.stop();
     }
+    // 主动删除的机器人下次启动不再恢复
+    if let Err(err) = delete_session(uin, protocol).await {
+        tracing::error!("failed to delete session: {}", err);
+    }
+}
+
+// 按 uin 查找机器人，忽略协议
+pub fn get_bot(uin: i64) -> Option<Arc<Bot>> {
+    BOTS.iter()
+        .find(|b| b.key().0 == uin)
+        .map(|b| b.value().clone())
+}
+
+pub fn bot_ids() -> Vec<i64> {
+    BOTS.iter().map(|b| b.key().0).collect()
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
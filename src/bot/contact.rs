@@ -0,0 +1,230 @@
+use std::sync::{Arc, Mutex};
+
+use cached::{Cached, TimedSizedCache};
+use dashmap::DashMap;
+use ricq::handler::QEvent;
+use ricq::structs::{FriendInfo, GroupMemberInfo, GroupMemberPermission};
+use ricq::Client;
+
+use crate::error::{RCError, RCResult};
+
+const LOAD_ATTEMPT_SIZE: usize = 10000;
+// 同一个群或成员的加载间隔，秒，失败时也不重复请求
+const LOAD_INTERVAL: u64 = 600;
+
+// 好友和群成员缓存，用于补全事件中的 sender
+// 好友登录时加载，群成员在第一次使用时按群加载，之后根据事件更新
+pub struct ContactCache {
+    friends: DashMap<i64, FriendInfo>,
+    // group_code -> uin -> member
+    groups: DashMap<i64, DashMap<i64, GroupMemberInfo>>,
+    // 最近加载过的 (group_code, uin)，加载整个群时 uin 为 0
+    load_attempts: Mutex<TimedSizedCache<(i64, i64), ()>>,
+}
+
+impl Default for ContactCache {
+    fn default() -> Self {
+        Self {
+            friends: Default::default(),
+            groups: Default::default(),
+            load_attempts: Mutex::new(TimedSizedCache::with_size_and_lifespan(
+                LOAD_ATTEMPT_SIZE,
+                LOAD_INTERVAL,
+            )),
+        }
+    }
+}
+
+impl ContactCache {
+    pub async fn load_friends(&self, client: &Arc<Client>) -> RCResult<()> {
+        for friend in client.get_friend_list().await?.friends {
+            self.friends.insert(friend.uin, friend);
+        }
+        tracing::info!("contact cache loaded {} friends", self.friends.len());
+        Ok(())
+    }
+
+    pub fn friend(&self, uin: i64) -> Option<FriendInfo> {
+        self.friends.get(&uin).map(|f| f.clone())
+    }
+
+    // 重新获取整个群的成员列表
+    pub async fn reload_group(
+        &self,
+        client: &Arc<Client>,
+        group_code: i64,
+    ) -> RCResult<Vec<GroupMemberInfo>> {
+        let group = client
+            .get_group_info(group_code)
+            .await?
+            .ok_or(RCError::NotFound("group"))?;
+        let members = client
+            .get_group_member_list(group_code, group.owner_uin)
+            .await?;
+        self.groups.insert(
+            group_code,
+            members.iter().map(|m| (m.uin, m.clone())).collect(),
+        );
+        Ok(members)
+    }
+
+    pub async fn group_members(
+        &self,
+        client: &Arc<Client>,
+        group_code: i64,
+    ) -> RCResult<Vec<GroupMemberInfo>> {
+        if let Some(members) = self.groups.get(&group_code) {
+            return Ok(members.iter().map(|m| m.clone()).collect());
+        }
+        self.reload_group(client, group_code).await
+    }
+
+    // 群未加载时加载整个群，群内找不到时单独获取该成员
+    pub async fn group_member(
+        &self,
+        client: &Arc<Client>,
+        group_code: i64,
+        uin: i64,
+    ) -> RCResult<GroupMemberInfo> {
+        if !self.groups.contains_key(&group_code) {
+            self.reload_group(client, group_code).await?;
+        }
+        if let Some(member) = self.cached_member(group_code, uin) {
+            return Ok(member);
+        }
+        self.reload_member(client, group_code, uin).await
+    }
+
+    pub async fn reload_member(
+        &self,
+        client: &Arc<Client>,
+        group_code: i64,
+        uin: i64,
+    ) -> RCResult<GroupMemberInfo> {
+        let member = client.get_group_member_info(group_code, uin).await?;
+        if let Some(members) = self.groups.get(&group_code) {
+            members.insert(uin, member.clone());
+        }
+        Ok(member)
+    }
+
+    pub fn cached_member(&self, group_code: i64, uin: i64) -> Option<GroupMemberInfo> {
+        self.groups
+            .get(&group_code)
+            .and_then(|members| members.get(&uin).map(|m| m.clone()))
+    }
+
+    // 缓存中没有时在后台加载，本次不等待，避免阻塞事件循环
+    pub fn cached_member_or_load(
+        self: &Arc<Self>,
+        client: &Arc<Client>,
+        group_code: i64,
+        uin: i64,
+    ) -> Option<GroupMemberInfo> {
+        let member = self.cached_member(group_code, uin);
+        if member.is_none() && self.should_load(group_code, uin) {
+            self.start_load_member(client, group_code, uin);
+        }
+        member
+    }
+
+    fn start_load_member(self: &Arc<Self>, client: &Arc<Client>, group_code: i64, uin: i64) {
+        let (cache, client) = (self.clone(), client.clone());
+        tokio::spawn(async move {
+            if let Err(err) = cache.load_member(&client, group_code, uin).await {
+                tracing::warn!("failed to load group member: {}", err);
+            }
+        });
+    }
+
+    // LOAD_INTERVAL 内同一个群或成员只加载一次
+    fn should_load(&self, group_code: i64, uin: i64) -> bool {
+        let key = if self.groups.contains_key(&group_code) {
+            (group_code, uin)
+        } else {
+            (group_code, 0)
+        };
+        let mut attempts = self.load_attempts.lock().unwrap();
+        if attempts.cache_get(&key).is_some() {
+            return false;
+        }
+        attempts.cache_set(key, ());
+        true
+    }
+
+    // 群未加载时加载整个群，否则单独获取该成员
+    pub async fn load_member(
+        &self,
+        client: &Arc<Client>,
+        group_code: i64,
+        uin: i64,
+    ) -> RCResult<()> {
+        if self.groups.contains_key(&group_code) {
+            self.reload_member(client, group_code, uin).await?;
+        } else {
+            self.reload_group(client, group_code).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn handle_event(self: &Arc<Self>, client: &Arc<Client>, event: &QEvent) {
+        match event {
+            QEvent::NewFriend(e) => {
+                self.friends.insert(e.inner.uin, e.inner.clone());
+            }
+            QEvent::DeleteFriend(e) => {
+                self.friends.remove(&e.inner.uin);
+            }
+            // 群已加载时在后台获取新成员，未加载时第一次使用整个群加载
+            QEvent::NewMember(e) => {
+                if self.groups.contains_key(&e.inner.group_code)
+                    && self.should_load(e.inner.group_code, e.inner.member_uin)
+                {
+                    self.start_load_member(client, e.inner.group_code, e.inner.member_uin);
+                }
+            }
+            QEvent::GroupLeave(e) => {
+                if e.inner.member_uin == client.uin().await {
+                    self.groups.remove(&e.inner.group_code);
+                } else if let Some(members) = self.groups.get(&e.inner.group_code) {
+                    members.remove(&e.inner.member_uin);
+                }
+            }
+            QEvent::MemberPermissionChange(e) => {
+                if let Some(members) = self.groups.get(&e.inner.group_code) {
+                    if let Some(mut member) = members.get_mut(&e.inner.member_uin) {
+                        member.permission = e.inner.new_permission.clone();
+                    }
+                }
+            }
+            QEvent::GroupMessage(e) => {
+                if let Some(members) = self.groups.get(&e.inner.group_code) {
+                    if let Some(mut member) = members.get_mut(&e.inner.from_uin) {
+                        member.card_name = e.inner.group_card.clone();
+                        member.last_speak_time = e.inner.time as i64;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 0 男，1 女
+pub fn gender_to_sex(gender: u8) -> String {
+    match gender {
+        0 => "male",
+        1 => "female",
+        _ => "unknown",
+    }
+    .into()
+}
+
+pub fn permission_to_role(permission: &GroupMemberPermission) -> String {
+    match permission {
+        GroupMemberPermission::Owner => "owner",
+        GroupMemberPermission::Administrator => "admin",
+        GroupMemberPermission::Member => "member",
+    }
+    .into()
+}
@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use rand::prelude::StdRng;
+use rand::SeedableRng;
+use ricq::device::Device;
+
+use crate::error::{RCError, RCResult};
+use crate::util::private_file::write_private_file;
+
+pub const DEVICE_PATH: &str = "data/devices";
+
+fn device_file(uin: i64) -> PathBuf {
+    Path::new(DEVICE_PATH).join(format!("{}.json", uin))
+}
+
+pub async fn load_device(uin: i64) -> RCResult<Option<Device>> {
+    let path = device_file(uin);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let device = serde_json::from_slice(&tokio::fs::read(path).await?)
+        .map_err(|e| RCError::Other(format!("failed to parse device {}: {}", uin, e)))?;
+    Ok(Some(device))
+}
+
+pub async fn save_device(uin: i64, device: &Device) -> RCResult<()> {
+    tokio::fs::create_dir_all(DEVICE_PATH).await?;
+    write_private_file(
+        device_file(uin),
+        &serde_json::to_vec_pretty(device).map_err(|e| RCError::Other(e.to_string()))?,
+    )
+    .await?;
+    Ok(())
+}
+
+// 登录后保存，已有设备时不覆盖，避免扫码登录的随机设备替换之前保存或导入的设备
+pub async fn save_device_if_absent(uin: i64, device: &Device) -> RCResult<()> {
+    match load_device(uin).await? {
+        None => save_device(uin, device).await,
+        Some(saved) => {
+            if serde_json::to_value(&saved).ok() != serde_json::to_value(device).ok() {
+                tracing::warn!(
+                    "login device of {} differs from saved device, keep saved device",
+                    uin
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+// 优先使用保存的设备，没有时用 seed 生成并保存
+pub async fn load_or_create_device(uin: i64, seed: u64) -> RCResult<Device> {
+    if let Some(device) = load_device(uin).await? {
+        return Ok(device);
+    }
+    let device = Device::random_with_rng(&mut StdRng::seed_from_u64(seed));
+    save_device(uin, &device).await?;
+    tracing::info!("device created: {}", uin);
+    Ok(device)
+}
+
+pub async fn list_device_uins() -> RCResult<Vec<i64>> {
+    tokio::fs::create_dir_all(DEVICE_PATH).await?;
+    let mut dir = tokio::fs::read_dir(DEVICE_PATH).await?;
+    let mut uins = Vec::new();
+    while let Some(e) = dir.next_entry().await? {
+        if let Some(uin) = e
+            .file_name()
+            .to_str()
+            .and_then(|n| n.strip_suffix(".json"))
+            .and_then(|n| n.parse().ok())
+        {
+            uins.push(uin);
+        }
+    }
+    uins.sort_unstable();
+    Ok(uins)
+}
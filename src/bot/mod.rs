@@ -1,39 +1,75 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use cached::Cached;
+use cached::{Cached, SizedCache, TimedSizedCache};
 use ricq::client::NetworkStatus;
+use ricq::device::Device;
 use ricq::handler::QEvent;
 use ricq::Client;
-use ricq_core::structs::GroupMemberPermission;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::broadcast;
 
-use crate::error::RCResult;
-use crate::event::to_proto_event;
+use crate::bot::bots::shared_plugin_connections;
+use crate::bot::contact::ContactCache;
+use crate::bot::msg_store::MsgStore;
+use crate::event::{to_proto_event, to_proto_group_card, to_proto_lifecycle};
+use crate::idl::pbbot;
 use crate::plugin::conn::PluginConnection;
 use crate::plugin::Plugin;
 
 pub mod bots;
+pub mod contact;
+pub mod device;
+pub mod msg_store;
+pub mod session;
+pub mod web;
+
+const TEMP_SESSION_SIZE: usize = 10000;
+// 临时会话保留时间，秒
+const TEMP_SESSION_LIFESPAN: u64 = 86400;
+const MEMBER_CARD_SIZE: usize = 100000;
 
 pub struct Bot {
     pub client: Arc<Client>,
+    // 登录使用的设备信息
+    pub device: Device,
     pub plugin_connections: HashMap<String, Arc<PluginConnection>>,
     pub stop_channel: broadcast::Sender<()>,
-    pub group_role_cache: Mutex<cached::TimedCache<(i64, i64), GroupMemberPermission>>,
+    // 转换后的事件，供 gRPC 订阅
+    pub event_channel: broadcast::Sender<pbbot::frame::Data>,
+    pub msg_store: MsgStore,
+    // 非好友的临时会话，user_id -> group_id
+    pub temp_sessions: Mutex<TimedSizedCache<i64, i64>>,
+    // 最近收到的群名片，(group_id, user_id) -> card，用于检测群名片变化
+    pub member_cards: Mutex<SizedCache<(i64, i64), String>>,
+    // 上次推送的在线状态
+    pub online: AtomicBool,
+    // 好友和群成员，用于补全事件 sender
+    pub contacts: Arc<ContactCache>,
 }
 
 impl Bot {
-    pub fn new(client: Arc<Client>, plugins: Vec<Plugin>) -> Self {
+    pub fn new(client: Arc<Client>, uin: i64, device: Device, plugins: Vec<Plugin>) -> Self {
         let (stop_channel, _) = broadcast::channel(1);
+        let (event_channel, _) = broadcast::channel(128);
         Self {
             client,
+            device,
             stop_channel,
+            event_channel,
+            msg_store: MsgStore::new(uin),
+            temp_sessions: Mutex::new(TimedSizedCache::with_size_and_lifespan(
+                TEMP_SESSION_SIZE,
+                TEMP_SESSION_LIFESPAN,
+            )),
+            member_cards: Mutex::new(SizedCache::with_size(MEMBER_CARD_SIZE)),
+            online: AtomicBool::new(true),
+            contacts: Default::default(),
             plugin_connections: plugins
                 .into_iter()
                 .map(|p| (p.name.clone(), Arc::new(PluginConnection::new(p))))
                 .collect(),
-            group_role_cache: Mutex::new(cached::TimedCache::with_lifespan(30)),
         }
     }
 
@@ -46,11 +82,17 @@ impl Bot {
                 tokio::select! {
                     e = event_receiver.recv() => {
                         if let Ok(e) = e {
+                            let offline_reason = match &e {
+                                QEvent::KickedOffline(k) => Some(format!("kicked offline: {:?}", k.inner)),
+                                QEvent::MSFOffline(m) => Some(format!("msf offline: {:?}", m.inner)),
+                                _ => None,
+                            };
+                            if let Some(reason) = offline_reason {
+                                bot.set_online(false, &reason).await;
+                            }
+                            bot.contacts.handle_event(&bot.client, &e).await;
                             if let Some(e) = to_proto_event(&bot, e).await {
-                                for (_, plugin) in bot.plugin_connections.iter() {
-                                    // TODO convert event
-                                    plugin.handle_event(bot.client.uin().await,e.clone()).await;
-                                }
+                                bot.handle_proto_event(e).await;
                             }
                         }
                     }
@@ -62,29 +104,92 @@ impl Bot {
         });
     }
 
-    // 连接插件地址
-    pub fn start_plugins(self: &Arc<Self>) {
-        for (name, p) in self.plugin_connections.iter() {
-            let name = name.clone();
-            let plugin = p.clone();
-
-            let bot = self.clone();
-            tokio::spawn(async move {
-                // TODO stop?
-                let mut stop_signal = plugin.stop_channel.subscribe();
-                loop {
-                    tokio::select! {
-                        reason = plugin.start(&bot) => {
-                            // 阻塞到断开
-                            tracing::warn!("plugin [{}] error: {:?}", name, reason);
-                        }
-                        _ = stop_signal.recv() => {
-                            break;
-                        }
+    // 后台加载好友列表
+    pub fn start_load_friends(self: &Arc<Self>) {
+        let bot = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = bot.contacts.load_friends(&bot.client).await {
+                tracing::error!("failed to load friends: {}", err);
+            }
+        });
+    }
+
+    // 定时检查连接状态，变化时推送 online/offline
+    pub fn start_status_watcher(self: &Arc<Self>) {
+        let bot = self.clone();
+        let mut stop_signal = self.stop_channel.subscribe();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let online = bot.client.get_status() == NetworkStatus::Running as u8;
+                        bot.set_online(online, "network offline").await;
+                    }
+                    _ = stop_signal.recv() => {
+                        break;
                     }
-                    tokio::time::sleep(Duration::from_secs(5)).await;
                 }
-            });
+            }
+        });
+    }
+
+    // 在线状态变化时推送 lifecycle 事件，reason 为离线原因
+    pub async fn set_online(&self, online: bool, reason: &str) {
+        if self.online.swap(online, Ordering::Relaxed) == online {
+            return;
+        }
+        let (sub_type, reason) = if online {
+            ("online", "")
+        } else {
+            ("offline", reason)
+        };
+        tracing::info!("bot lifecycle {} {}", sub_type, reason);
+        self.dispatch_lifecycle(sub_type, reason).await;
+    }
+
+    pub async fn dispatch_lifecycle(&self, sub_type: &str, reason: &str) {
+        let self_id = self.client.uin().await;
+        self.dispatch_event(pbbot::frame::Data::LifecycleMetaEvent(to_proto_lifecycle(
+            self_id, sub_type, reason,
+        )))
+        .await;
+    }
+
+    // 跳过已经推送过的自己的消息，群消息检测群名片变化
+    pub async fn handle_proto_event(self: &Arc<Self>, e: pbbot::frame::Data) {
+        if self.msg_store.is_sent_by_self(&e).await {
+            return;
+        }
+        let card_event = match &e {
+            pbbot::frame::Data::GroupMessageEvent(m) => {
+                to_proto_group_card(self, m).map(pbbot::frame::Data::GroupCardNoticeEvent)
+            }
+            _ => None,
+        };
+        self.dispatch_event(e).await;
+        if let Some(card_event) = card_event {
+            self.dispatch_event(card_event).await;
+        }
+    }
+
+    // 保存消息，推送给 gRPC 订阅和插件
+    pub async fn dispatch_event(&self, e: pbbot::frame::Data) {
+        self.msg_store.insert_event(&e).await;
+        self.event_channel.send(e.clone()).ok();
+        let bot_id = self.client.uin().await;
+        for (_, plugin) in self.plugin_connections.iter() {
+            plugin.handle_event(bot_id, e.clone()).await;
+        }
+        for plugin in shared_plugin_connections() {
+            plugin.handle_event(bot_id, e.clone()).await;
+        }
+    }
+
+    // 连接插件地址
+    pub fn start_plugins(self: &Arc<Self>) {
+        for (_, p) in self.plugin_connections.iter() {
+            p.spawn(Some(self.clone()));
         }
     }
 
@@ -97,31 +202,24 @@ impl Bot {
         self.client.stop(NetworkStatus::Stop);
     }
 
-    pub async fn cached_group_role(
-        &self,
-        group_id: i64,
-        user_id: i64,
-    ) -> RCResult<GroupMemberPermission> {
-        if let Some(role) = self
-            .group_role_cache
+    pub fn temp_session(&self, user_id: i64) -> Option<i64> {
+        self.temp_sessions
             .lock()
-            .await
-            .cache_get(&(group_id, user_id))
+            .unwrap()
+            .cache_get(&user_id)
             .cloned()
-        {
-            return Ok(role);
-        }
-        let admins = self.client.get_group_admin_list(group_id).await?;
-        let user_permission = admins.get(&user_id).cloned().unwrap_or_default();
-        let mut cache = self.group_role_cache.lock().await;
-        for (uin, permission) in admins {
-            cache.cache_set((group_id, uin), permission);
-        }
-        if cache.cache_misses().unwrap_or_default() > 100 {
-            cache.flush();
-            cache.cache_reset_metrics();
-        }
-        Ok(user_permission)
+    }
+
+    pub fn set_temp_session(&self, user_id: i64, group_id: i64) {
+        self.temp_sessions
+            .lock()
+            .unwrap()
+            .cache_set(user_id, group_id);
+    }
+
+    // 成为好友后不再使用临时会话
+    pub fn remove_temp_session(&self, user_id: i64) {
+        self.temp_sessions.lock().unwrap().cache_remove(&user_id);
     }
 }
 
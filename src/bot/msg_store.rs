@@ -0,0 +1,351 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::RwLock;
+
+use cached::{Cached, TimedSizedCache};
+use lazy_static::lazy_static;
+use tokio::sync::Mutex;
+
+use crate::error::RCResult;
+use crate::idl::pbbot;
+use crate::idl::pbbot::{get_msg_resp, GetMsgResp, MessageReceipt};
+use crate::plugin::pb_to_bytes::PbToBytes;
+
+const CACHE_SIZE: usize = 10000;
+const PRUNE_INTERVAL: u32 = 1000;
+
+pub struct MsgStoreConfig {
+    // 消息保留时间，秒
+    pub retention: u64,
+    // 不为 None 时同时保存到磁盘
+    pub db: Option<sled::Db>,
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<MsgStoreConfig> = RwLock::new(MsgStoreConfig {
+        retention: 86400,
+        db: None,
+    });
+}
+
+// 启动时调用，path 为 None 时只保存在内存
+pub fn init_msg_store(path: Option<&str>, retention: u64) -> RCResult<()> {
+    let db = match path {
+        Some(path) => Some(sled::open(path)?),
+        None => None,
+    };
+    *CONFIG.write().unwrap() = MsgStoreConfig { retention, db };
+    Ok(())
+}
+
+// 退出前写入磁盘
+pub async fn flush_msg_store() -> RCResult<()> {
+    let db = CONFIG.read().unwrap().db.clone();
+    if let Some(db) = db {
+        db.flush_async().await?;
+    }
+    Ok(())
+}
+
+// 记录收到和发送的消息，供 GetMsgReq 查询
+pub struct MsgStore {
+    cache: Mutex<TimedSizedCache<String, GetMsgResp>>,
+    // 私聊 (sender, seq) -> key，回复只有 seq 时查找
+    private_keys: Mutex<TimedSizedCache<(i64, i32), String>>,
+    // 已经推送过 message_sent 的消息 key
+    sent: Mutex<TimedSizedCache<String, ()>>,
+    tree: Option<sled::Tree>,
+    // 时间索引，key 为 time(u64 BE) + 消息 key，用于按时间范围清理
+    index: Option<sled::Tree>,
+    retention: u64,
+    insert_count: AtomicU32,
+}
+
+impl MsgStore {
+    pub fn new(uin: i64) -> Self {
+        let config = CONFIG.read().unwrap();
+        Self::open(uin, config.db.as_ref(), config.retention)
+    }
+
+    fn open(uin: i64, db: Option<&sled::Db>, retention: u64) -> Self {
+        Self {
+            cache: Mutex::new(TimedSizedCache::with_size_and_lifespan(
+                CACHE_SIZE, retention,
+            )),
+            private_keys: Mutex::new(TimedSizedCache::with_size_and_lifespan(
+                CACHE_SIZE, retention,
+            )),
+            sent: Mutex::new(TimedSizedCache::with_size_and_lifespan(
+                CACHE_SIZE, retention,
+            )),
+            tree: open_tree(db, uin.to_string()),
+            index: open_tree(db, format!("{}:time", uin)),
+            retention,
+            insert_count: AtomicU32::new(0),
+        }
+    }
+
+    pub async fn insert(&self, msg: GetMsgResp) {
+        let key = match msg.message_id.as_ref() {
+            Some(receipt) if receipt.group_id == 0 => {
+                let key = msg_key(receipt);
+                self.private_keys
+                    .lock()
+                    .await
+                    .cache_set((receipt.sender_id, first_seq(&msg.message_id)), key.clone());
+                key
+            }
+            Some(receipt) => msg_key(receipt),
+            None => return,
+        };
+        if let (Some(tree), Some(index)) = (self.tree.as_ref(), self.index.as_ref()) {
+            if let Err(err) = tree
+                .insert(key.as_bytes(), msg.to_bytes())
+                .and_then(|_| index.insert(index_key(msg.time as i64, &key), &[]))
+            {
+                tracing::error!("failed to save message: {}", err);
+            }
+            if self.insert_count.fetch_add(1, Ordering::Relaxed) % PRUNE_INTERVAL == 0 {
+                let (tree, index) = (tree.clone(), index.clone());
+                let cutoff = chrono::Utc::now().timestamp() - self.retention as i64;
+                tokio::task::spawn_blocking(move || prune(&tree, &index, cutoff));
+            }
+        }
+        self.cache.lock().await.cache_set(key, msg);
+    }
+
+    pub async fn get(&self, receipt: &MessageReceipt) -> Option<GetMsgResp> {
+        self.get_by_key(&msg_key(receipt)).await
+    }
+
+    // 私聊消息 key 包含时间，只知道 sender 和 seq 时使用，有多条时返回最新的
+    pub async fn get_private(&self, sender_id: i64, seq: i32) -> Option<GetMsgResp> {
+        let key = self
+            .private_keys
+            .lock()
+            .await
+            .cache_get(&(sender_id, seq))
+            .cloned();
+        if let Some(key) = key {
+            return self.get_by_key(&key).await;
+        }
+        let prefix = format!("p:{}:{}:", sender_id, seq);
+        self.tree
+            .as_ref()?
+            .scan_prefix(prefix.as_bytes())
+            .values()
+            .flatten()
+            .filter_map(|value| GetMsgResp::from_bytes(&value).ok())
+            .filter(|msg| !self.expired(msg))
+            .max_by_key(|msg| msg.time)
+    }
+
+    async fn get_by_key(&self, key: &str) -> Option<GetMsgResp> {
+        if let Some(msg) = self.cache.lock().await.cache_get(key).cloned() {
+            return Some(msg);
+        }
+        let msg = GetMsgResp::from_bytes(&self.tree.as_ref()?.get(key.as_bytes()).ok()??).ok()?;
+        if self.expired(&msg) {
+            return None;
+        }
+        Some(msg)
+    }
+
+    fn expired(&self, msg: &GetMsgResp) -> bool {
+        chrono::Utc::now().timestamp() - msg.time as i64 > self.retention as i64
+    }
+
+    // 自己发送的消息，pbrq 发送后和服务器推送各有一次，不确定哪个先到，先到的推送，后到的跳过
+    // 返回 true 时已经推送过
+    pub async fn mark_sent(&self, receipt: &MessageReceipt) -> bool {
+        self.sent
+            .lock()
+            .await
+            .cache_set(msg_key(receipt), ())
+            .is_some()
+    }
+
+    // 服务器推送的自己的消息，pbrq 发送时已经推送过则跳过
+    pub async fn is_sent_by_self(&self, event: &pbbot::frame::Data) -> bool {
+        let receipt = match event {
+            pbbot::frame::Data::GroupMessageEvent(e) if e.post_type == "message_sent" => {
+                e.message_id.as_ref()
+            }
+            pbbot::frame::Data::PrivateMessageEvent(e) if e.post_type == "message_sent" => {
+                e.message_id.as_ref()
+            }
+            _ => None,
+        };
+        match receipt {
+            Some(receipt) => self.mark_sent(receipt).await,
+            None => false,
+        }
+    }
+
+    pub async fn insert_event(&self, event: &pbbot::frame::Data) {
+        match event {
+            pbbot::frame::Data::GroupMessageEvent(e) => self.insert(group_msg_record(e)).await,
+            pbbot::frame::Data::PrivateMessageEvent(e) => self.insert(private_msg_record(e)).await,
+            _ => {}
+        }
+    }
+}
+
+fn open_tree(db: Option<&sled::Db>, name: String) -> Option<sled::Tree> {
+    db?.open_tree(name)
+        .map_err(|err| tracing::error!("failed to open msg store tree: {}", err))
+        .ok()
+}
+
+fn index_key(time: i64, key: &str) -> Vec<u8> {
+    let mut index_key = (time.max(0) as u64).to_be_bytes().to_vec();
+    index_key.extend_from_slice(key.as_bytes());
+    index_key
+}
+
+// 按时间索引删除 cutoff 之前的消息，同一个 key 被新消息覆盖时保留
+fn prune(tree: &sled::Tree, index: &sled::Tree, cutoff: i64) {
+    for (index_key, _) in index
+        .range(..(cutoff.max(0) as u64).to_be_bytes())
+        .flatten()
+    {
+        let key = &index_key[8..];
+        let expired = match tree.get(key) {
+            Ok(Some(value)) => GetMsgResp::from_bytes(&value)
+                .map(|msg| (msg.time as i64) < cutoff)
+                .unwrap_or(true),
+            _ => false,
+        };
+        if expired {
+            tree.remove(key).ok();
+        }
+        index.remove(index_key).ok();
+    }
+}
+
+// 群消息 seq 在群内唯一，私聊消息加上时间区分
+fn msg_key(receipt: &MessageReceipt) -> String {
+    let seq = receipt.seqs.first().cloned().unwrap_or_default();
+    if receipt.group_id != 0 {
+        format!("g:{}:{}", receipt.group_id, seq)
+    } else {
+        format!("p:{}:{}:{}", receipt.sender_id, seq, receipt.time)
+    }
+}
+
+pub fn group_msg_record(event: &pbbot::GroupMessageEvent) -> GetMsgResp {
+    let sender = event.sender.clone().unwrap_or_default();
+    GetMsgResp {
+        time: event.time as i32,
+        message_type: event.message_type.clone(),
+        real_id: first_seq(&event.message_id),
+        sender: Some(get_msg_resp::Sender {
+            user_id: sender.user_id,
+            nickname: sender.nickname,
+            card: sender.card,
+            sex: sender.sex,
+            age: sender.age,
+            area: sender.area,
+            level: sender.level,
+            role: sender.role,
+            title: sender.title,
+        }),
+        message: event.message.clone(),
+        raw_message: event.raw_message.clone(),
+        message_id: event.message_id.clone(),
+        group_id: event.group_id,
+    }
+}
+
+pub fn private_msg_record(event: &pbbot::PrivateMessageEvent) -> GetMsgResp {
+    let sender = event.sender.clone().unwrap_or_default();
+    GetMsgResp {
+        time: event.time as i32,
+        message_type: event.message_type.clone(),
+        real_id: first_seq(&event.message_id),
+        sender: Some(get_msg_resp::Sender {
+            user_id: sender.user_id,
+            nickname: sender.nickname,
+            sex: sender.sex,
+            age: sender.age,
+            ..Default::default()
+        }),
+        message: event.message.clone(),
+        raw_message: event.raw_message.clone(),
+        message_id: event.message_id.clone(),
+        group_id: 0,
+    }
+}
+
+fn first_seq(receipt: &Option<MessageReceipt>) -> i32 {
+    receipt
+        .as_ref()
+        .and_then(|r| r.seqs.first().cloned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(time: i32, seq: i32) -> GetMsgResp {
+        GetMsgResp {
+            time,
+            message_id: Some(MessageReceipt {
+                sender_id: 1,
+                time: time as i64,
+                seqs: vec![seq],
+                rands: vec![],
+                group_id: 2,
+            }),
+            group_id: 2,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_private_by_seq() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut record = msg(now as i32, 5);
+        record.group_id = 0;
+        record.message_id.as_mut().unwrap().group_id = 0;
+        let store = MsgStore::open(1, Some(&db), 3600);
+        store.insert(record.clone()).await;
+        assert_eq!(store.get_private(1, 5).await, Some(record.clone()));
+        assert_eq!(store.get_private(2, 5).await, None);
+        // 内存中没有时从磁盘按前缀查找
+        let store = MsgStore::open(1, Some(&db), 3600);
+        assert_eq!(store.get_private(1, 5).await, Some(record));
+        assert_eq!(store.get_private(1, 6).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_mark_sent() {
+        let store = MsgStore::open(1, None, 3600);
+        let receipt = msg(100, 7).message_id.unwrap();
+        assert!(!store.mark_sent(&receipt).await);
+        assert!(store.mark_sent(&receipt).await);
+    }
+
+    #[test]
+    fn test_prune() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree("msg").unwrap();
+        let index = db.open_tree("index").unwrap();
+        let insert = |msg: GetMsgResp| {
+            let key = msg_key(msg.message_id.as_ref().unwrap());
+            tree.insert(key.as_bytes(), msg.to_bytes()).unwrap();
+            index.insert(index_key(msg.time as i64, &key), &[]).unwrap();
+        };
+        insert(msg(100, 1));
+        insert(msg(200, 2));
+        // seq 3 被新消息覆盖，旧索引过期时不删除
+        insert(msg(100, 3));
+        insert(msg(300, 3));
+        prune(&tree, &index, 250);
+        assert!(tree.get("g:2:1").unwrap().is_none());
+        assert!(tree.get("g:2:2").unwrap().is_none());
+        assert!(tree.get("g:2:3").unwrap().is_some());
+        assert_eq!(index.len(), 1);
+    }
+}
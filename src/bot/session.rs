@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ricq::client::{Connector, DefaultConnector, NetworkStatus};
+use ricq::ext::reconnect::Credential;
+use ricq::version::{get_version, Protocol};
+use ricq::{Client, LoginResponse};
+use ricq_core::token::Token;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::bots::on_login;
+use crate::bot::device::load_device;
+use crate::bot::Bot;
+use crate::error::{RCError, RCResult};
+use crate::handler::ConvertU8;
+use crate::util::private_file::write_private_file;
+
+pub const SESSION_PATH: &str = "data/sessions";
+
+// 登录成功后保存，启动时用 token 和保存的设备恢复登录
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub uin: i64,
+    pub protocol: u8,
+    pub token: Token,
+}
+
+fn session_file(uin: i64, protocol: u8) -> PathBuf {
+    Path::new(SESSION_PATH).join(format!("{}_{}.json", uin, protocol))
+}
+
+pub async fn save_session(session: &Session) -> RCResult<()> {
+    tokio::fs::create_dir_all(SESSION_PATH).await?;
+    write_private_file(
+        session_file(session.uin, session.protocol),
+        &serde_json::to_vec(session).map_err(|e| RCError::Other(e.to_string()))?,
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn save_bot_session(bot: &Bot) -> RCResult<()> {
+    save_session(&Session {
+        uin: bot.client.uin().await,
+        protocol: bot.client.version().await.protocol.to_u8(),
+        token: bot.client.gen_token().await,
+    })
+    .await
+}
+
+pub async fn delete_session(uin: i64, protocol: u8) -> RCResult<()> {
+    let path = session_file(uin, protocol);
+    if path.exists() {
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(())
+}
+
+pub async fn load_sessions() -> RCResult<Vec<Session>> {
+    tokio::fs::create_dir_all(SESSION_PATH).await?;
+    let mut dir = tokio::fs::read_dir(SESSION_PATH).await?;
+    let mut sessions = Vec::new();
+    while let Some(e) = dir.next_entry().await? {
+        if !e.path().extension().unwrap_or_default().eq("json") {
+            continue;
+        }
+        match serde_json::from_slice::<Session>(&tokio::fs::read(e.path()).await?) {
+            Ok(session) => sessions.push(session),
+            Err(err) => tracing::warn!("failed to parse session {:?}: {}", e.path(), err),
+        }
+    }
+    Ok(sessions)
+}
+
+// 恢复所有保存的登录，失败的需要重新在网页登录
+pub async fn restore_sessions() {
+    let sessions = match load_sessions().await {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            tracing::error!("failed to load sessions: {}", err);
+            return;
+        }
+    };
+    for session in sessions {
+        let uin = session.uin;
+        if let Err(err) = restore_session(session).await {
+            tracing::warn!("failed to restore session {}: {}", uin, err);
+        }
+    }
+}
+
+async fn restore_session(session: Session) -> RCResult<()> {
+    let device = load_device(session.uin)
+        .await?
+        .ok_or(RCError::NotFound("device"))?;
+    let protocol = Protocol::from_u8(session.protocol);
+    let (sender, receiver) = tokio::sync::broadcast::channel(10);
+    let cli = Arc::new(Client::new(device.clone(), get_version(protocol), sender));
+    let stream = DefaultConnector.connect(&cli).await?;
+    let c = cli.clone();
+    let network_join_handle = tokio::spawn(async move { c.start(stream).await });
+    tokio::task::yield_now().await;
+    let resp = cli.token_login(session.token.clone()).await;
+    if !matches!(resp, Ok(LoginResponse::Success(_))) {
+        cli.stop(NetworkStatus::Stop);
+        return Err(RCError::Other(format!("token login failed: {:?}", resp)));
+    }
+    tracing::info!(
+        "restore session success: {} {}",
+        session.uin,
+        session.protocol
+    );
+    let credential = Credential::Token(cli.gen_token().await);
+    on_login(cli, receiver, device, credential, network_join_handle).await;
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::bot::Bot;
+use crate::error::{RCError, RCResult};
+
+impl Bot {
+    // 网页接口 cookie，domain 为 p_skey 对应域名或其子域名时带上 p_skey
+    pub async fn cookies(&self, domain: &str) -> String {
+        let uin = self.client.uin().await;
+        let engine = self.client.engine.read().await;
+        let sig = &engine.transport.sig;
+        let mut cookies = format!(
+            "uin=o{}; skey={}; p_uin=o{};",
+            uin,
+            String::from_utf8_lossy(&sig.s_key),
+            uin
+        );
+        if let Some((_, ps_key)) = sig
+            .ps_key_map
+            .iter()
+            .find(|(k, _)| domain == k.as_str() || domain.ends_with(&format!(".{}", k)))
+        {
+            cookies.push_str(&format!(" p_skey={};", String::from_utf8_lossy(ps_key)));
+        }
+        cookies
+    }
+
+    // bkn
+    pub async fn csrf_token(&self) -> i32 {
+        csrf_token(&self.client.engine.read().await.transport.sig.s_key)
+    }
+
+    // 带 cookie 和 bkn 请求网页接口，retcode 不为 0 时返回错误
+    pub async fn web_post(
+        &self,
+        url: &str,
+        form: &[(&str, String)],
+    ) -> RCResult<serde_json::Value> {
+        let mut form = form.to_vec();
+        form.push(("bkn", self.csrf_token().await.to_string()));
+        let resp = web_client()?
+            .post(url)
+            .header("Cookie", self.cookies(url_host(url)).await)
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+        check_retcode(url, resp)
+    }
+
+    pub async fn web_get(
+        &self,
+        url: &str,
+        query: &[(&str, String)],
+    ) -> RCResult<serde_json::Value> {
+        let mut query = query.to_vec();
+        query.push(("bkn", self.csrf_token().await.to_string()));
+        let resp = web_client()?
+            .get(url)
+            .header("Cookie", self.cookies(url_host(url)).await)
+            .query(&query)
+            .send()
+            .await?
+            .json()
+            .await?;
+        check_retcode(url, resp)
+    }
+}
+
+fn web_client() -> RCResult<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()?)
+}
+
+fn url_host(url: &str) -> &str {
+    let host = url.split("://").nth(1).unwrap_or(url);
+    host.split(['/', '?']).next().unwrap_or(host)
+}
+
+fn check_retcode(url: &str, resp: serde_json::Value) -> RCResult<serde_json::Value> {
+    let retcode = resp
+        .get("retcode")
+        .or_else(|| resp.get("ec"))
+        .or_else(|| resp.get("code"))
+        .and_then(|c| c.as_i64())
+        .unwrap_or_default();
+    if retcode != 0 {
+        return Err(RCError::Other(format!("web api {} error: {}", url, resp)));
+    }
+    Ok(resp)
+}
+
+pub fn csrf_token(s_key: &[u8]) -> i32 {
+    let mut hash: i32 = 5381;
+    for b in s_key {
+        hash = hash.wrapping_add((hash << 5).wrapping_add(*b as i32));
+    }
+    hash & 0x7fffffff
+}
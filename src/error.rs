@@ -13,6 +13,10 @@ pub enum RCError {
     Other(String),
     #[error("none error {0}")]
     None(&'static str),
+    #[error("not_found error {0}")]
+    NotFound(&'static str),
+    #[error("api not supported")]
+    Unsupported,
     #[error("timeout error")]
     Timeout,
     #[error("client_not_found error")]
@@ -35,6 +39,12 @@ pub enum RCError {
     InvalidUri(#[from] tokio_tungstenite::tungstenite::http::uri::InvalidUri),
     #[error("tungstenite http error, {0}")]
     TungsteniteHttp(#[from] tokio_tungstenite::tungstenite::http::Error),
+    #[error("sled error, {0}")]
+    Sled(#[from] sled::Error),
+    #[error("script parse error, {0}")]
+    ScriptParse(#[from] rhai::ParseError),
+    #[error("audio error, {0}")]
+    Audio(String),
 }
 
 impl IntoResponse for RCError {
@@ -42,8 +52,21 @@ impl IntoResponse for RCError {
         let code = match self {
             Self::ClientNotFound => StatusCode::BAD_REQUEST,
             Self::ProtocolNotSupported => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
         (code, self.to_string()).into_response()
     }
 }
+
+impl From<RCError> for tonic::Status {
+    fn from(err: RCError) -> Self {
+        match err {
+            RCError::ClientNotFound => tonic::Status::not_found(err.to_string()),
+            RCError::None(_) => tonic::Status::invalid_argument(err.to_string()),
+            RCError::NotFound(_) => tonic::Status::not_found(err.to_string()),
+            RCError::Unsupported => tonic::Status::unimplemented(err.to_string()),
+            _ => tonic::Status::internal(err.to_string()),
+        }
+    }
+}
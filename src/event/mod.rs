@@ -1,23 +1,43 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
+use cached::Cached;
 use ricq::client::event::{
-    FriendMessageEvent, FriendMessageRecallEvent, GroupLeaveEvent, GroupMessageEvent,
-    GroupMessageRecallEvent, GroupMuteEvent, JoinGroupRequestEvent, MemberPermissionChangeEvent,
-    NewFriendEvent, NewFriendRequestEvent, NewMemberEvent, SelfInvitedEvent,
+    DeleteFriendEvent, FriendAudioMessageEvent, FriendMessageEvent, FriendMessageRecallEvent,
+    FriendPokeEvent, GroupAudioMessageEvent, GroupLeaveEvent, GroupMessageEvent,
+    GroupMessageRecallEvent, GroupMuteEvent, GroupNameUpdateEvent, GroupPokeEvent,
+    JoinGroupRequestEvent, MemberPermissionChangeEvent, NewFriendEvent, NewFriendRequestEvent,
+    NewMemberEvent, SelfInvitedEvent, TempMessageEvent,
 };
 use ricq::handler::QEvent;
+use ricq::msg::elem::{self, RQElem};
+use ricq::msg::MessageChain;
 use ricq::structs::GroupMemberPermission;
+use ricq_core::pb::msg::elem::Elem;
 
+use crate::bot::contact::{gender_to_sex, permission_to_role};
 use crate::bot::Bot;
 use crate::idl::pbbot;
 use crate::idl::pbbot::MessageReceipt;
-use crate::msg::to_proto_chain;
 use crate::msg::to_xml::proto_to_xml;
+use crate::msg::{append_record, set_reply_group_id, to_proto_chain};
+use crate::util::group_file::{decode_group_file, MsgFile, FILE_TRANS_ELEM_TYPE};
 
 pub async fn to_proto_event(bot: &Arc<Bot>, event: QEvent) -> Option<pbbot::frame::Data> {
     match event {
         QEvent::GroupMessage(e) => {
+            if let Some(file) = find_group_file(&e.inner.elements) {
+                tracing::info!(
+                    "GROUP_UPLOAD (GROUP={}): {} {:?}",
+                    e.inner.group_code,
+                    e.inner.from_uin,
+                    file.file_name
+                );
+                return Some(pbbot::frame::Data::GroupUploadNoticeEvent(
+                    to_proto_group_upload(e, file).await,
+                ));
+            }
             tracing::info!(
                 "MESSAGE (GROUP={}): {}",
                 e.inner.group_code,
@@ -33,11 +53,44 @@ pub async fn to_proto_event(bot: &Arc<Bot>, event: QEvent) -> Option<pbbot::fram
                 e.inner.from_uin,
                 e.inner.elements
             );
+            bot.remove_temp_session(e.inner.from_uin);
             Some(pbbot::frame::Data::PrivateMessageEvent(
                 to_proto_private_message(bot, e).await,
             ))
         }
-        // QEvent::TempMessage(_) => {}
+        // 获取语音 url 需要请求服务器，在后台转换后推送，不阻塞事件循环
+        QEvent::GroupAudioMessage(e) => {
+            tracing::info!("AUDIO (GROUP={}): {}", e.inner.group_code, e.inner.from_uin);
+            let bot = bot.clone();
+            tokio::spawn(async move {
+                let event = to_proto_group_audio_message(&bot, e).await;
+                bot.handle_proto_event(pbbot::frame::Data::GroupMessageEvent(event))
+                    .await;
+            });
+            None
+        }
+        QEvent::FriendAudioMessage(e) => {
+            tracing::info!("AUDIO (FRIEND={})", e.inner.from_uin);
+            let bot = bot.clone();
+            tokio::spawn(async move {
+                let event = to_proto_private_audio_message(&bot, e).await;
+                bot.handle_proto_event(pbbot::frame::Data::PrivateMessageEvent(event))
+                    .await;
+            });
+            None
+        }
+        QEvent::TempMessage(e) => {
+            tracing::info!(
+                "MESSAGE (TEMP={} GROUP={}): {}",
+                e.inner.from_uin,
+                e.inner.group_code,
+                e.inner.elements
+            );
+            bot.set_temp_session(e.inner.from_uin, e.inner.group_code);
+            Some(pbbot::frame::Data::PrivateMessageEvent(
+                to_proto_temp_message(bot, e).await,
+            ))
+        }
         QEvent::GroupRequest(e) => {
             tracing::info!(
                 "GROUP_REQUEST (GROUP={}): {}",
@@ -110,6 +163,7 @@ pub async fn to_proto_event(bot: &Arc<Bot>, event: QEvent) -> Option<pbbot::fram
         }
         QEvent::NewFriend(e) => {
             tracing::info!("NEW_FRIEND (FRIEND={}): {}", e.inner.uin, e.inner.nick);
+            bot.remove_temp_session(e.inner.uin);
             Some(pbbot::frame::Data::FriendAddNoticeEvent(
                 to_proto_friend_add(bot, e).await,
             ))
@@ -124,9 +178,44 @@ pub async fn to_proto_event(bot: &Arc<Bot>, event: QEvent) -> Option<pbbot::fram
                 to_proto_group_decrease(bot, e).await,
             ))
         }
-        // QEvent::FriendPoke(_) => {}
-        // QEvent::GroupNameUpdate(_) => {}
-        // QEvent::DeleteFriend(_) => {}
+        QEvent::FriendPoke(e) => {
+            tracing::info!(
+                "FRIEND_POKE (FRIEND={}): {}",
+                e.inner.sender,
+                e.inner.receiver
+            );
+            Some(pbbot::frame::Data::FriendPokeNoticeEvent(
+                to_proto_friend_poke(bot, e).await,
+            ))
+        }
+        QEvent::GroupPoke(e) => {
+            tracing::info!(
+                "GROUP_POKE (GROUP={}): {} {}",
+                e.inner.group_code,
+                e.inner.sender,
+                e.inner.receiver
+            );
+            Some(pbbot::frame::Data::GroupPokeNoticeEvent(
+                to_proto_group_poke(bot, e).await,
+            ))
+        }
+        QEvent::GroupNameUpdate(e) => {
+            tracing::info!(
+                "GROUP_NAME_UPDATE (GROUP={}): {} {}",
+                e.inner.group_code,
+                e.inner.operator_uin,
+                e.inner.group_name
+            );
+            Some(pbbot::frame::Data::GroupNameUpdateNoticeEvent(
+                to_proto_group_name_update(bot, e).await,
+            ))
+        }
+        QEvent::DeleteFriend(e) => {
+            tracing::info!("DELETE_FRIEND (FRIEND={})", e.inner.uin);
+            Some(pbbot::frame::Data::FriendDeleteNoticeEvent(
+                to_proto_friend_delete(bot, e).await,
+            ))
+        }
         QEvent::MemberPermissionChange(e) => {
             tracing::info!(
                 "PERMISSION_CHANGE (GROUP={}): {} {:?}",
@@ -142,16 +231,59 @@ pub async fn to_proto_event(bot: &Arc<Bot>, event: QEvent) -> Option<pbbot::fram
     }
 }
 
+pub fn event_frame_type(event: &pbbot::frame::Data) -> pbbot::frame::FrameType {
+    use pbbot::frame::{Data, FrameType};
+    match event {
+        Data::PrivateMessageEvent(_) => FrameType::TPrivateMessageEvent,
+        Data::GroupMessageEvent(_) => FrameType::TGroupMessageEvent,
+        Data::GroupUploadNoticeEvent(_) => FrameType::TGroupUploadNoticeEvent,
+        Data::GroupAdminNoticeEvent(_) => FrameType::TGroupAdminNoticeEvent,
+        Data::GroupDecreaseNoticeEvent(_) => FrameType::TGroupDecreaseNoticeEvent,
+        Data::GroupIncreaseNoticeEvent(_) => FrameType::TGroupIncreaseNoticeEvent,
+        Data::GroupBanNoticeEvent(_) => FrameType::TGroupBanNoticeEvent,
+        Data::FriendAddNoticeEvent(_) => FrameType::TFriendAddNoticeEvent,
+        Data::GroupRecallNoticeEvent(_) => FrameType::TGroupRecallNoticeEvent,
+        Data::FriendRecallNoticeEvent(_) => FrameType::TFriendRecallNoticeEvent,
+        Data::FriendRequestEvent(_) => FrameType::TFriendRequestEvent,
+        Data::GroupRequestEvent(_) => FrameType::TGroupRequestEvent,
+        Data::HelloMetaEvent(_) => FrameType::THelloMetaEvent,
+        Data::FriendPokeNoticeEvent(_) => FrameType::TFriendPokeNoticeEvent,
+        Data::GroupPokeNoticeEvent(_) => FrameType::TGroupPokeNoticeEvent,
+        Data::GroupNameUpdateNoticeEvent(_) => FrameType::TGroupNameUpdateNoticeEvent,
+        Data::FriendDeleteNoticeEvent(_) => FrameType::TFriendDeleteNoticeEvent,
+        Data::GroupCardNoticeEvent(_) => FrameType::TGroupCardNoticeEvent,
+        Data::LifecycleMetaEvent(_) => FrameType::TLifecycleMetaEvent,
+        Data::HeartbeatMetaEvent(_) => FrameType::THeartbeatMetaEvent,
+        _ => FrameType::Tunknown,
+    }
+}
+
+// 机器人自己发送的消息
+pub fn is_message_sent(event: &pbbot::frame::Data) -> bool {
+    match event {
+        pbbot::frame::Data::GroupMessageEvent(e) => e.post_type == "message_sent",
+        pbbot::frame::Data::PrivateMessageEvent(e) => e.post_type == "message_sent",
+        _ => false,
+    }
+}
+
 pub async fn to_proto_group_message(
     bot: &Arc<Bot>,
     event: GroupMessageEvent,
 ) -> pbbot::GroupMessageEvent {
     let client = event.client;
     let message = event.inner;
-    let role = bot
-        .cached_group_role(message.group_code, message.from_uin)
-        .await
-        .unwrap_or_default();
+    let anonymous = message.elements.clone().into_iter().find_map(|e| match e {
+        RQElem::Anonymous(a) => Some(to_proto_anonymous(message.from_uin, a)),
+        _ => None,
+    });
+    let sender = to_proto_group_sender(
+        bot,
+        message.group_code,
+        message.from_uin,
+        message.group_card.clone(),
+        anonymous.is_some(),
+    );
     let message_id = MessageReceipt {
         sender_id: message.from_uin,
         time: message.time as i64,
@@ -159,39 +291,306 @@ pub async fn to_proto_group_message(
         rands: message.rands,
         group_id: message.group_code,
     };
+    let mut proto_message = to_proto_chain(&client, message.elements);
+    set_reply_group_id(&mut proto_message, message.group_code);
+    let raw_message = proto_to_xml(proto_message.clone());
+    let self_id = client.uin().await;
+    pbbot::GroupMessageEvent {
+        time: message.time as i64,
+        self_id,
+        // 其他客户端发送的消息
+        post_type: if message.from_uin == self_id {
+            "message_sent"
+        } else {
+            "message"
+        }
+        .to_string(),
+        message_type: "group".to_string(),
+        sub_type: if anonymous.is_some() {
+            "anonymous"
+        } else {
+            "normal"
+        }
+        .to_string(),
+        message_id: Some(message_id),
+        group_id: message.group_code,
+        user_id: message.from_uin,
+        anonymous,
+        raw_message,
+        message: proto_message,
+        sender: Some(sender),
+        font: 0,
+        extra: Default::default(),
+    }
+}
+
+// 匿名消息的 user_id 不是真实成员，不查询
+// 缓存中没有时 role、sex 为空，不能当作普通成员
+fn to_proto_group_sender(
+    bot: &Arc<Bot>,
+    group_code: i64,
+    user_id: i64,
+    card: String,
+    anonymous: bool,
+) -> pbbot::group_message_event::Sender {
+    let member = if anonymous {
+        None
+    } else {
+        bot.contacts
+            .cached_member_or_load(&bot.client, group_code, user_id)
+    };
+    match member {
+        Some(member) => pbbot::group_message_event::Sender {
+            user_id,
+            nickname: member.nickname,
+            card,
+            sex: gender_to_sex(member.gender),
+            age: 0,
+            area: "".into(),
+            level: member.level.to_string(),
+            role: permission_to_role(&member.permission),
+            title: member.special_title,
+        },
+        None => pbbot::group_message_event::Sender {
+            user_id,
+            card,
+            ..Default::default()
+        },
+    }
+}
+
+// 消息中没有昵称时使用好友缓存
+fn to_proto_private_sender(
+    bot: &Arc<Bot>,
+    user_id: i64,
+    nickname: String,
+) -> pbbot::private_message_event::Sender {
+    let nickname = if nickname.is_empty() {
+        bot.contacts
+            .friend(user_id)
+            .map(|f| f.nick)
+            .unwrap_or_default()
+    } else {
+        nickname
+    };
+    pbbot::private_message_event::Sender {
+        user_id,
+        nickname,
+        sex: "unknown".into(),
+        age: 0,
+    }
+}
+
+fn to_proto_temp_sender(
+    bot: &Arc<Bot>,
+    group_code: i64,
+    user_id: i64,
+    nickname: String,
+) -> pbbot::private_message_event::Sender {
+    match bot
+        .contacts
+        .cached_member_or_load(&bot.client, group_code, user_id)
+    {
+        Some(member) => pbbot::private_message_event::Sender {
+            user_id,
+            nickname: if nickname.is_empty() {
+                member.nickname
+            } else {
+                nickname
+            },
+            sex: gender_to_sex(member.gender),
+            age: 0,
+        },
+        None => to_proto_private_sender(bot, user_id, nickname),
+    }
+}
+
+// flag 为 base64(anon_id)|nick，用于 SetGroupAnonymousBanReq
+fn to_proto_anonymous(
+    from_uin: i64,
+    anonymous: elem::Anonymous,
+) -> pbbot::group_message_event::Anonymous {
+    pbbot::group_message_event::Anonymous {
+        id: from_uin,
+        flag: format!("{}|{}", base64::encode(&anonymous.anon_id), anonymous.nick),
+        name: anonymous.nick,
+    }
+}
+
+pub async fn to_proto_private_message(
+    bot: &Arc<Bot>,
+    event: FriendMessageEvent,
+) -> pbbot::PrivateMessageEvent {
+    let client = event.client;
+    let message = event.inner;
+    let message_id = MessageReceipt {
+        sender_id: message.from_uin,
+        time: message.time as i64,
+        seqs: message.seqs,
+        rands: message.rands,
+        group_id: 0,
+    };
     let proto_message = to_proto_chain(&client, message.elements);
     let raw_message = proto_to_xml(proto_message.clone());
+    let self_id = client.uin().await;
+    // 其他客户端发送的消息，target_id 为私聊对象
+    let (post_type, extra) = if message.from_uin == self_id {
+        (
+            "message_sent",
+            BTreeMap::from([("target_id".into(), message.target.to_string())]),
+        )
+    } else {
+        ("message", Default::default())
+    };
+    pbbot::PrivateMessageEvent {
+        time: message.time as i64,
+        self_id,
+        post_type: post_type.to_string(),
+        message_type: "private".to_string(),
+        sub_type: "normal".to_string(),
+        message_id: Some(message_id),
+        user_id: message.from_uin,
+        raw_message,
+        message: proto_message,
+        sender: Some(to_proto_private_sender(
+            bot,
+            message.from_uin,
+            message.from_nick,
+        )),
+        font: 0,
+        extra,
+    }
+}
+
+// 群文件上传以群消息收到
+fn find_group_file(elements: &MessageChain) -> Option<MsgFile> {
+    elements.clone().into_iter().find_map(|e| match e {
+        RQElem::Other(e) => match *e {
+            Elem::TransElemInfo(t) if t.elem_type == Some(FILE_TRANS_ELEM_TYPE) => {
+                decode_group_file(&t.elem_value?)
+            }
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+// url 不在事件中获取，避免阻塞事件循环，需要时调用 GetGroupFileUrl
+pub async fn to_proto_group_upload(
+    event: GroupMessageEvent,
+    file: MsgFile,
+) -> pbbot::GroupUploadNoticeEvent {
+    let client = event.client;
+    let message = event.inner;
+    let id = String::from_utf8_lossy(&file.file_path.unwrap_or_default()).to_string();
+    let busid = file.bus_id.unwrap_or_default() as i64;
+    pbbot::GroupUploadNoticeEvent {
+        time: message.time as i64,
+        self_id: client.uin().await,
+        post_type: "notice".to_string(),
+        notice_type: "group_upload".to_string(),
+        group_id: message.group_code,
+        user_id: message.from_uin,
+        file: Some(pbbot::group_upload_notice_event::File {
+            id,
+            name: file.file_name.unwrap_or_default(),
+            size: file.file_size.unwrap_or_default() as i64,
+            busid,
+            url: String::new(),
+        }),
+        extra: Default::default(),
+    }
+}
+
+pub async fn to_proto_group_audio_message(
+    bot: &Arc<Bot>,
+    event: GroupAudioMessageEvent,
+) -> pbbot::GroupMessageEvent {
+    let client = event.client;
+    let message = event.inner;
+    let sender = to_proto_group_sender(
+        bot,
+        message.group_code,
+        message.from_uin,
+        message.group_card.clone(),
+        false,
+    );
+    let url = client
+        .get_group_audio_url(message.group_code, message.audio)
+        .await
+        .map_err(|err| tracing::error!("failed to get group audio url: {}", err))
+        .unwrap_or_default();
+    let mut proto_message = Vec::new();
+    append_record(&mut proto_message, url);
+    let raw_message = proto_to_xml(proto_message.clone());
     pbbot::GroupMessageEvent {
         time: message.time as i64,
         self_id: client.uin().await,
         post_type: "message".to_string(),
         message_type: "group".to_string(),
         sub_type: "normal".to_string(),
-        message_id: Some(message_id),
+        message_id: Some(MessageReceipt {
+            sender_id: message.from_uin,
+            time: message.time as i64,
+            seqs: message.seqs,
+            rands: message.rands,
+            group_id: message.group_code,
+        }),
         group_id: message.group_code,
         user_id: message.from_uin,
-        anonymous: None, // TODO
+        anonymous: None,
         raw_message,
         message: proto_message,
-        sender: Some(pbbot::group_message_event::Sender {
-            user_id: message.from_uin,
-            card: message.group_card,
-            role: match role {
-                GroupMemberPermission::Owner => "owner",
-                GroupMemberPermission::Administrator => "admin",
-                GroupMemberPermission::Member => "member",
-            }
-            .into(),
-            ..Default::default()
+        sender: Some(sender),
+        font: 0,
+        extra: Default::default(),
+    }
+}
+
+pub async fn to_proto_private_audio_message(
+    bot: &Arc<Bot>,
+    event: FriendAudioMessageEvent,
+) -> pbbot::PrivateMessageEvent {
+    let client = event.client;
+    let message = event.inner;
+    let url = client
+        .get_friend_audio_url(message.from_uin, message.audio)
+        .await
+        .map_err(|err| tracing::error!("failed to get friend audio url: {}", err))
+        .unwrap_or_default();
+    let mut proto_message = Vec::new();
+    append_record(&mut proto_message, url);
+    let raw_message = proto_to_xml(proto_message.clone());
+    pbbot::PrivateMessageEvent {
+        time: message.time as i64,
+        self_id: client.uin().await,
+        post_type: "message".to_string(),
+        message_type: "private".to_string(),
+        sub_type: "normal".to_string(),
+        message_id: Some(MessageReceipt {
+            sender_id: message.from_uin,
+            time: message.time as i64,
+            seqs: message.seqs,
+            rands: message.rands,
+            group_id: 0,
         }),
+        user_id: message.from_uin,
+        raw_message,
+        message: proto_message,
+        sender: Some(to_proto_private_sender(
+            bot,
+            message.from_uin,
+            message.from_nick,
+        )),
         font: 0,
         extra: Default::default(),
     }
 }
 
-pub async fn to_proto_private_message(
-    _: &Arc<Bot>,
-    event: FriendMessageEvent,
+// 临时会话，extra.group_id 为来源群
+pub async fn to_proto_temp_message(
+    bot: &Arc<Bot>,
+    event: TempMessageEvent,
 ) -> pbbot::PrivateMessageEvent {
     let client = event.client;
     let message = event.inner;
@@ -209,18 +608,19 @@ pub async fn to_proto_private_message(
         self_id: client.uin().await,
         post_type: "message".to_string(),
         message_type: "private".to_string(),
-        sub_type: "normal".to_string(),
+        sub_type: "group".to_string(),
         message_id: Some(message_id),
         user_id: message.from_uin,
         raw_message,
         message: proto_message,
-        sender: Some(pbbot::private_message_event::Sender {
-            user_id: message.from_uin,
-            nickname: message.from_nick,
-            ..Default::default()
-        }),
+        sender: Some(to_proto_temp_sender(
+            bot,
+            message.group_code,
+            message.from_uin,
+            message.from_nick,
+        )),
         font: 0,
-        extra: Default::default(),
+        extra: BTreeMap::from([("group_id".into(), message.group_code.to_string())]),
     }
 }
 
@@ -409,7 +809,7 @@ pub async fn to_proto_self_group_request(
         user_id: client.uin().await,
         comment: "".into(),
         flag,
-        extra: HashMap::from([("invitor_uin".to_string(), request.invitor_uin.to_string())]),
+        extra: BTreeMap::from([("invitor_uin".to_string(), request.invitor_uin.to_string())]),
     }
 }
 
@@ -456,3 +856,137 @@ pub async fn to_proto_group_admin_notice(
         extra: Default::default(),
     }
 }
+
+pub async fn to_proto_friend_poke(
+    _: &Arc<Bot>,
+    event: FriendPokeEvent,
+) -> pbbot::FriendPokeNoticeEvent {
+    let client = event.client;
+    let poke = event.inner;
+
+    pbbot::FriendPokeNoticeEvent {
+        time: chrono::Utc::now().timestamp(),
+        self_id: client.uin().await,
+        post_type: "notice".to_string(),
+        notice_type: "notify".to_string(),
+        sub_type: "poke".to_string(),
+        user_id: poke.sender,
+        target_id: poke.receiver,
+        extra: Default::default(),
+    }
+}
+
+pub async fn to_proto_group_poke(
+    _: &Arc<Bot>,
+    event: GroupPokeEvent,
+) -> pbbot::GroupPokeNoticeEvent {
+    let client = event.client;
+    let poke = event.inner;
+
+    pbbot::GroupPokeNoticeEvent {
+        time: chrono::Utc::now().timestamp(),
+        self_id: client.uin().await,
+        post_type: "notice".to_string(),
+        notice_type: "notify".to_string(),
+        sub_type: "poke".to_string(),
+        group_id: poke.group_code,
+        user_id: poke.sender,
+        target_id: poke.receiver,
+        extra: Default::default(),
+    }
+}
+
+pub async fn to_proto_group_name_update(
+    _: &Arc<Bot>,
+    event: GroupNameUpdateEvent,
+) -> pbbot::GroupNameUpdateNoticeEvent {
+    let client = event.client;
+    let update = event.inner;
+
+    pbbot::GroupNameUpdateNoticeEvent {
+        time: chrono::Utc::now().timestamp(),
+        self_id: client.uin().await,
+        post_type: "notice".to_string(),
+        notice_type: "group_name".to_string(),
+        group_id: update.group_code,
+        operator_id: update.operator_uin,
+        name: update.group_name,
+        extra: Default::default(),
+    }
+}
+
+pub async fn to_proto_friend_delete(
+    _: &Arc<Bot>,
+    event: DeleteFriendEvent,
+) -> pbbot::FriendDeleteNoticeEvent {
+    let client = event.client;
+
+    pbbot::FriendDeleteNoticeEvent {
+        time: chrono::Utc::now().timestamp(),
+        self_id: client.uin().await,
+        post_type: "notice".to_string(),
+        notice_type: "friend_delete".to_string(),
+        user_id: event.inner.uin,
+        extra: Default::default(),
+    }
+}
+
+// 和上次收到的群名片不同时产生 GroupCardNoticeEvent，第一次收到只记录
+pub fn to_proto_group_card(
+    bot: &Arc<Bot>,
+    event: &pbbot::GroupMessageEvent,
+) -> Option<pbbot::GroupCardNoticeEvent> {
+    if event.anonymous.is_some() {
+        return None;
+    }
+    let card = event.sender.as_ref()?.card.clone();
+    let card_old = bot
+        .member_cards
+        .lock()
+        .unwrap()
+        .cache_set((event.group_id, event.user_id), card.clone())?;
+    if card_old == card {
+        return None;
+    }
+    Some(pbbot::GroupCardNoticeEvent {
+        time: event.time,
+        self_id: event.self_id,
+        post_type: "notice".to_string(),
+        notice_type: "group_card".to_string(),
+        group_id: event.group_id,
+        user_id: event.user_id,
+        card_old,
+        card_new: card,
+        extra: Default::default(),
+    })
+}
+
+// sub_type: connect, online, offline, reconnecting
+pub fn to_proto_lifecycle(self_id: i64, sub_type: &str, reason: &str) -> pbbot::LifecycleMetaEvent {
+    pbbot::LifecycleMetaEvent {
+        time: chrono::Utc::now().timestamp(),
+        self_id,
+        post_type: "meta_event".to_string(),
+        meta_event_type: "lifecycle".to_string(),
+        sub_type: sub_type.to_string(),
+        reason: reason.to_string(),
+        extra: Default::default(),
+    }
+}
+
+pub fn to_proto_heartbeat(
+    self_id: i64,
+    interval: Duration,
+    online: bool,
+) -> pbbot::HeartbeatMetaEvent {
+    pbbot::HeartbeatMetaEvent {
+        time: chrono::Utc::now().timestamp(),
+        self_id,
+        post_type: "meta_event".to_string(),
+        meta_event_type: "heartbeat".to_string(),
+        interval: interval.as_millis() as i64,
+        online,
+        good: online,
+        extra: Default::default(),
+    }
+}
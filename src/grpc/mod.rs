@@ -0,0 +1,132 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::api_handler::call_api;
+use crate::bot::bots::get_bot;
+use crate::bot::Bot;
+use crate::event::event_frame_type;
+use crate::idl::pbbot::frame::Data;
+use crate::idl::pbbot::one_bot_server::{OneBot, OneBotServer};
+use crate::idl::pbbot::*;
+
+#[derive(Default)]
+pub struct GrpcServer;
+
+// signal 完成后停止接收新请求
+pub async fn serve(
+    addr: SocketAddr,
+    signal: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(OneBotServer::new(GrpcServer))
+        .serve_with_shutdown(addr, signal)
+        .await
+}
+
+// 从 metadata x-self-id 获取机器人
+fn request_bot<T>(request: &Request<T>) -> Result<Arc<Bot>, Status> {
+    let bot_id: i64 = request
+        .metadata()
+        .get("x-self-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| Status::invalid_argument("x-self-id is required"))?;
+    get_bot(bot_id).ok_or_else(|| Status::not_found(format!("bot {} not found", bot_id)))
+}
+
+macro_rules! one_bot_service {
+    ($($name:ident($req:ident) -> $resp:ident;)*) => {
+        #[tonic::async_trait]
+        impl OneBot for GrpcServer {
+            $(
+                async fn $name(&self, request: Request<$req>) -> Result<Response<$resp>, Status> {
+                    let bot = request_bot(&request)?;
+                    match call_api(&bot, Data::$req(request.into_inner()), "grpc").await? {
+                        Data::$resp(resp) => Ok(Response::new(resp)),
+                        _ => Err(Status::internal("unexpected api resp")),
+                    }
+                }
+            )*
+
+            type SubscribeEventsStream = Pin<Box<dyn Stream<Item = Result<Frame, Status>> + Send>>;
+
+            async fn subscribe_events(
+                &self,
+                request: Request<SubscribeEventsReq>,
+            ) -> Result<Response<Self::SubscribeEventsStream>, Status> {
+                let req = request.into_inner();
+                let bot = get_bot(req.bot_id)
+                    .ok_or_else(|| Status::not_found(format!("bot {} not found", req.bot_id)))?;
+                let bot_id = req.bot_id;
+                let filter = req.filter;
+                let stream = BroadcastStream::new(bot.event_channel.subscribe()).filter_map(
+                    move |event| {
+                        // 消费太慢丢弃的事件直接跳过
+                        let event = event.ok()?;
+                        let frame_type = event_frame_type(&event) as i32;
+                        if !filter.is_empty() && !filter.contains(&frame_type) {
+                            return None;
+                        }
+                        Some(Ok(Frame {
+                            bot_id,
+                            frame_type,
+                            echo: "".into(),
+                            ok: true,
+                            data: Some(event),
+                            extra: Default::default(),
+                        }))
+                    },
+                );
+                Ok(Response::new(Box::pin(stream)))
+            }
+        }
+    };
+}
+
+one_bot_service! {
+    send_private_msg(SendPrivateMsgReq) -> SendPrivateMsgResp;
+    send_group_msg(SendGroupMsgReq) -> SendGroupMsgResp;
+    send_msg(SendMsgReq) -> SendMsgResp;
+    delete_msg(DeleteMsgReq) -> DeleteMsgResp;
+    get_msg(GetMsgReq) -> GetMsgResp;
+    get_forward_msg(GetForwardMsgReq) -> GetForwardMsgResp;
+    send_like(SendLikeReq) -> SendLikeResp;
+    set_group_kick(SetGroupKickReq) -> SetGroupKickResp;
+    set_group_ban(SetGroupBanReq) -> SetGroupBanResp;
+    set_group_anonymous_ban(SetGroupAnonymousBanReq) -> SetGroupAnonymousBanResp;
+    set_group_whole_ban(SetGroupWholeBanReq) -> SetGroupWholeBanResp;
+    set_group_admin(SetGroupAdminReq) -> SetGroupAdminResp;
+    set_group_anonymous(SetGroupAnonymousReq) -> SetGroupAnonymousResp;
+    set_group_card(SetGroupCardReq) -> SetGroupCardResp;
+    set_group_name(SetGroupNameReq) -> SetGroupNameResp;
+    set_group_leave(SetGroupLeaveReq) -> SetGroupLeaveResp;
+    set_group_special_title(SetGroupSpecialTitleReq) -> SetGroupSpecialTitleResp;
+    set_friend_add_request(SetFriendAddRequestReq) -> SetFriendAddRequestResp;
+    set_group_add_request(SetGroupAddRequestReq) -> SetGroupAddRequestResp;
+    get_login_info(GetLoginInfoReq) -> GetLoginInfoResp;
+    get_stranger_info(GetStrangerInfoReq) -> GetStrangerInfoResp;
+    get_friend_list(GetFriendListReq) -> GetFriendListResp;
+    get_group_info(GetGroupInfoReq) -> GetGroupInfoResp;
+    get_group_list(GetGroupListReq) -> GetGroupListResp;
+    get_group_member_info(GetGroupMemberInfoReq) -> GetGroupMemberInfoResp;
+    get_group_member_list(GetGroupMemberListReq) -> GetGroupMemberListResp;
+    get_group_honor_info(GetGroupHonorInfoReq) -> GetGroupHonorInfoResp;
+    get_cookies(GetCookiesReq) -> GetCookiesResp;
+    get_csrf_token(GetCsrfTokenReq) -> GetCsrfTokenResp;
+    get_credentials(GetCredentialsReq) -> GetCredentialsResp;
+    get_record(GetRecordReq) -> GetRecordResp;
+    can_send_record(CanSendRecordReq) -> CanSendRecordResp;
+    set_group_sign_in(SetGroupSignInReq) -> SetGroupSignInResp;
+    send_music(SendMusicReq) -> SendMusicResp;
+    send_poke(SendPokeReq) -> SendPokeResp;
+    upload_group_file(UploadGroupFileReq) -> UploadGroupFileResp;
+    get_group_file_url(GetGroupFileUrlReq) -> GetGroupFileUrlResp;
+    get_group_files(GetGroupFilesReq) -> GetGroupFilesResp;
+    delete_group_file(DeleteGroupFileReq) -> DeleteGroupFileResp;
+}
@@ -0,0 +1,78 @@
+use axum::Json;
+use ricq::device::Device;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::device::{list_device_uins, load_device, save_device};
+use crate::error::{RCError, RCResult};
+
+#[derive(Default, Serialize)]
+pub struct ListDeviceResp {
+    pub devices: Vec<ListDeviceRespDevice>,
+}
+
+#[derive(Default, Serialize)]
+pub struct ListDeviceRespDevice {
+    pub uin: i64,
+    pub display: String,
+    pub brand: String,
+    pub model: String,
+    pub imei: String,
+    pub android_id: String,
+}
+
+pub async fn list() -> RCResult<Json<ListDeviceResp>> {
+    let mut devices = Vec::new();
+    for uin in list_device_uins().await? {
+        if let Some(device) = load_device(uin).await? {
+            devices.push(ListDeviceRespDevice {
+                uin,
+                display: device.display,
+                brand: device.brand,
+                model: device.model,
+                imei: device.imei,
+                android_id: device.android_id,
+            });
+        }
+    }
+    Ok(Json(ListDeviceResp { devices }))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportDeviceReq {
+    pub uin: i64,
+}
+
+#[derive(Serialize)]
+pub struct ExportDeviceResp {
+    pub uin: i64,
+    pub device: Device,
+}
+
+pub async fn export(Json(req): Json<ExportDeviceReq>) -> RCResult<Json<ExportDeviceResp>> {
+    let device = load_device(req.uin)
+        .await?
+        .ok_or(RCError::NotFound("device"))?;
+    Ok(Json(ExportDeviceResp {
+        uin: req.uin,
+        device,
+    }))
+}
+
+// 导入后下次登录生效
+#[derive(Deserialize)]
+pub struct ImportDeviceReq {
+    pub uin: i64,
+    pub device: Device,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ImportDeviceResp {}
+
+pub async fn import(Json(req): Json<ImportDeviceReq>) -> RCResult<Json<ImportDeviceResp>> {
+    if req.uin == 0 {
+        return Err(RCError::None("uin"));
+    }
+    save_device(req.uin, &req.device).await?;
+    tracing::info!("device imported: {}", req.uin);
+    Ok(Json(ImportDeviceResp {}))
+}
@@ -1,6 +1,7 @@
 use ricq::version::Protocol;
 
 pub mod bot;
+pub mod device;
 pub mod password;
 pub mod plugins;
 pub mod qrcode;
@@ -3,7 +3,6 @@ use std::sync::Arc;
 use axum::Json;
 use dashmap::DashMap;
 use lazy_static::lazy_static;
-use rand::{prelude::StdRng, SeedableRng};
 use ricq::client::{Connector, DefaultConnector};
 use ricq::{
     client::NetworkStatus,
@@ -17,6 +16,7 @@ use serde::{Deserialize, Serialize};
 use tokio::task::JoinHandle;
 
 use crate::bot::bots::on_login;
+use crate::bot::device::load_or_create_device;
 use crate::error::{RCError, RCResult};
 use crate::handler::ConvertU8;
 
@@ -25,6 +25,7 @@ pub struct PasswordClient {
     pub login_response: LoginResponse,
     pub event_receiver: tokio::sync::broadcast::Receiver<QEvent>,
     pub network_join_handle: JoinHandle<()>,
+    pub device: Device,
     pub credential: Credential,
 }
 
@@ -117,10 +118,14 @@ pub async fn login(Json(req): Json<CreateClientReq>) -> RCResult<Json<PasswordLo
     if rand_seed == 0 {
         rand_seed = req.uin as u64;
     }
-    let device = Device::random_with_rng(&mut StdRng::seed_from_u64(rand_seed));
+    let device = load_or_create_device(req.uin, rand_seed).await?;
     let protocol = Protocol::from_u8(req.protocol);
     let (sender, receiver) = tokio::sync::broadcast::channel(10);
-    let cli = Arc::new(Client::new(device, get_version(protocol.clone()), sender));
+    let cli = Arc::new(Client::new(
+        device.clone(),
+        get_version(protocol.clone()),
+        sender,
+    ));
     let connector = DefaultConnector;
     let stream = connector.connect(&cli).await.map_err(RCError::IO)?;
     let c = cli.clone();
@@ -136,7 +141,7 @@ pub async fn login(Json(req): Json<CreateClientReq>) -> RCResult<Json<PasswordLo
     });
     if let LoginResponse::Success(_) = resp {
         tracing::info!("login success: {} {:?}", req.uin, req.protocol);
-        on_login(cli, receiver, credential, network_join_handle).await;
+        on_login(cli, receiver, device, credential, network_join_handle).await;
     } else if let Some(old) = CLIENTS.insert(
         (req.uin, protocol.to_u8()),
         PasswordClient {
@@ -144,6 +149,7 @@ pub async fn login(Json(req): Json<CreateClientReq>) -> RCResult<Json<PasswordLo
             login_response: resp.clone(),
             event_receiver: receiver,
             network_join_handle,
+            device,
             credential,
         },
     ) {
@@ -175,6 +181,7 @@ pub async fn submit_ticket(Json(req): Json<SubmitTicketReq>) -> RCResult<Json<Pa
             on_login(
                 client.client,
                 client.event_receiver,
+                client.device,
                 client.credential,
                 client.network_join_handle,
             )
@@ -230,6 +237,7 @@ pub async fn submit_sms(Json(req): Json<SubmitSmsReq>) -> RCResult<Json<Password
             on_login(
                 client.client,
                 client.event_receiver,
+                client.device,
                 client.credential,
                 client.network_join_handle,
             )
@@ -19,6 +19,7 @@ use serde::{Deserialize, Serialize};
 use tokio::task::JoinHandle;
 
 use crate::bot::bots::on_login;
+use crate::bot::device::load_or_create_device;
 use crate::error::{RCError, RCResult};
 use crate::handler::ConvertU8;
 
@@ -29,6 +30,7 @@ pub struct QRCodeClient {
     pub client: Arc<Client>,
     pub event_receiver: tokio::sync::broadcast::Receiver<QEvent>,
     pub network_join_handle: JoinHandle<()>,
+    pub device: Device,
 }
 
 lazy_static! {
@@ -60,6 +62,8 @@ mod base64 {
 pub struct CreateClientReq {
     pub device_seed: Option<u64>,
     pub protocol: u8,
+    // 指定账号时使用该账号保存的设备
+    pub uin: Option<i64>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -72,14 +76,17 @@ pub struct CreateClientResp {
 
 pub async fn create(Json(req): Json<CreateClientReq>) -> RCResult<Json<CreateClientResp>> {
     let rand_seed = req.device_seed.unwrap_or_else(rand::random);
-    let device = Device::random_with_rng(&mut StdRng::seed_from_u64(rand_seed));
+    let device = match req.uin {
+        Some(uin) if uin != 0 => load_or_create_device(uin, rand_seed).await?,
+        _ => Device::random_with_rng(&mut StdRng::seed_from_u64(rand_seed)),
+    };
     let protocol = match Protocol::from_u8(req.protocol) {
         Protocol::MacOS => Protocol::MacOS,
         Protocol::AndroidWatch => Protocol::AndroidWatch,
         _ => return Err(RCError::ProtocolNotSupported),
     };
     let (sender, receiver) = tokio::sync::broadcast::channel(10);
-    let cli = Arc::new(Client::new(device, get_version(protocol), sender));
+    let cli = Arc::new(Client::new(device.clone(), get_version(protocol), sender));
     let connector = DefaultConnector;
     let stream = connector.connect(&cli).await?;
     let c = cli.clone();
@@ -97,6 +104,7 @@ pub async fn create(Json(req): Json<CreateClientReq>) -> RCResult<Json<CreateCli
                 client: cli,
                 event_receiver: receiver,
                 network_join_handle,
+                device,
             },
         );
         Ok(Json(CreateClientResp {
@@ -161,6 +169,7 @@ pub async fn query(Json(req): Json<QueryQRCodeReq>) -> RCResult<Json<QueryQRCode
             on_login(
                 cli.client,
                 cli.event_receiver,
+                cli.device,
                 credential,
                 cli.network_join_handle,
             )
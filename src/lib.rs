@@ -3,6 +3,7 @@ pub mod api_handler;
 pub mod bot;
 pub mod error;
 pub mod event;
+pub mod grpc;
 pub mod handler;
 pub mod idl;
 pub mod msg;
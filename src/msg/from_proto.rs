@@ -1,15 +1,17 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::Arc;
 
 use async_recursion::async_recursion;
 use ricq::msg::{elem, MessageChain};
+use ricq::structs::{ForwardMessage, FriendAudio, GroupAudio, MessageNode};
 use ricq::Client;
 use ricq_core::hex::encode_hex;
 
-use crate::error::RCResult;
+use crate::error::{RCError, RCResult};
 use crate::idl::pbbot;
 use crate::msg::from_xml::xml_to_proto;
+use crate::util::audio::{encode_record, Record};
 use crate::util::uri_reader::{get_binary, read_binary_file};
 
 #[derive(Clone, Debug)]
@@ -40,8 +42,11 @@ pub async fn to_rq_chain(
                     chain.0.extend(ccc.0);
                 }
             }
+            "reply" => append_reply(&mut chain, element.data),
             "at" => append_at(&mut chain, element.data),
             "face" => append_face(&mut chain, element.data),
+            "json" => append_json(&mut chain, element.data),
+            "xml" => append_xml(&mut chain, element.data),
             "image" => {
                 if let Err(e) =
                     append_image(client, &mut chain, element.data, contact.clone()).await
@@ -64,11 +69,104 @@ pub async fn to_rq_chain(
     chain
 }
 
-pub fn append_text(chain: &mut MessageChain, mut data: HashMap<String, String>) {
+// node 合并为一条转发消息，data: user_id, name, time, content(xml)
+pub async fn to_rq_forward(
+    client: &Arc<Client>,
+    message: Vec<pbbot::Message>,
+    contact: Contact,
+) -> Vec<ForwardMessage> {
+    let mut nodes = Vec::new();
+    for mut element in message {
+        if element.r#type != "node" {
+            continue;
+        }
+        let content = element.data.remove("content").unwrap_or_default();
+        nodes.push(ForwardMessage::Message(MessageNode {
+            sender_id: element
+                .data
+                .remove("user_id")
+                .unwrap_or_default()
+                .parse()
+                .unwrap_or_default(),
+            time: element
+                .data
+                .remove("time")
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(|| chrono::Utc::now().timestamp() as i32),
+            sender_name: element.data.remove("name").unwrap_or_default(),
+            elements: to_rq_chain(client, xml_to_proto(content), contact.clone(), true).await,
+        }));
+    }
+    nodes
+}
+
+// 语音不能和其他元素一起发送，返回第一个 record 的 data
+pub fn find_record(message: &[pbbot::Message]) -> Option<BTreeMap<String, String>> {
+    message
+        .iter()
+        .find(|m| m.r#type == "record")
+        .map(|m| m.data.clone())
+}
+
+async fn load_record(mut data: BTreeMap<String, String>) -> RCResult<Record> {
+    let url = data.remove("url").unwrap_or_default();
+    let data = get_binary(&url).await?;
+    tokio::task::spawn_blocking(move || encode_record(data))
+        .await
+        .map_err(|e| RCError::Other(e.to_string()))?
+}
+
+pub async fn upload_group_record(
+    client: &Arc<Client>,
+    group_code: i64,
+    data: BTreeMap<String, String>,
+) -> RCResult<GroupAudio> {
+    let record = load_record(data).await?;
+    Ok(client
+        .upload_group_audio(group_code, record.data, record.codec)
+        .await?)
+}
+
+pub async fn upload_friend_record(
+    client: &Arc<Client>,
+    uin: i64,
+    data: BTreeMap<String, String>,
+) -> RCResult<FriendAudio> {
+    let record = load_record(data).await?;
+    Ok(client
+        .upload_friend_audio(uin, record.data, record.duration)
+        .await?)
+}
+
+pub fn append_text(chain: &mut MessageChain, mut data: BTreeMap<String, String>) {
     chain.push(elem::Text::new(data.remove("text").unwrap_or_default()))
 }
 
-pub fn append_at(chain: &mut MessageChain, mut data: HashMap<String, String>) {
+// seq 必填，user_id、time、text 为空时由 fill_reply 从消息记录补全
+pub fn append_reply(chain: &mut MessageChain, mut data: BTreeMap<String, String>) {
+    let mut elements = MessageChain::default();
+    elements.push(elem::Text::new(data.remove("text").unwrap_or_default()));
+    chain.push(elem::Reply {
+        reply_seq: data
+            .remove("seq")
+            .unwrap_or_default()
+            .parse()
+            .unwrap_or_default(),
+        sender: data
+            .remove("user_id")
+            .unwrap_or_default()
+            .parse()
+            .unwrap_or_default(),
+        time: data
+            .remove("time")
+            .unwrap_or_default()
+            .parse()
+            .unwrap_or_default(),
+        elements,
+    })
+}
+
+pub fn append_at(chain: &mut MessageChain, mut data: BTreeMap<String, String>) {
     let target = data
         .remove("qq")
         .unwrap_or_default()
@@ -83,7 +181,7 @@ pub fn append_at(chain: &mut MessageChain, mut data: HashMap<String, String>) {
     chain.push(elem::At { target, display })
 }
 
-pub fn append_face(chain: &mut MessageChain, mut data: HashMap<String, String>) {
+pub fn append_face(chain: &mut MessageChain, mut data: BTreeMap<String, String>) {
     chain.push(elem::Face::new(
         data.remove("id")
             .unwrap_or_default()
@@ -92,10 +190,27 @@ pub fn append_face(chain: &mut MessageChain, mut data: HashMap<String, String>)
     ))
 }
 
+pub fn append_json(chain: &mut MessageChain, mut data: BTreeMap<String, String>) {
+    chain.push(elem::LightApp {
+        content: data.remove("data").unwrap_or_default(),
+    })
+}
+
+// service_id 默认为 1
+pub fn append_xml(chain: &mut MessageChain, mut data: BTreeMap<String, String>) {
+    chain.push(elem::RichMsg {
+        service_id: data
+            .remove("service_id")
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(1),
+        template1: data.remove("data").unwrap_or_default(),
+    })
+}
+
 pub async fn append_image(
     client: &Arc<Client>,
     chain: &mut MessageChain,
-    mut data: HashMap<String, String>,
+    mut data: BTreeMap<String, String>,
     contact: Contact,
 ) -> RCResult<()> {
     let url = data.remove("url").unwrap_or_default();
@@ -110,7 +225,7 @@ pub async fn append_image(
 pub async fn append_video(
     client: &Arc<Client>,
     chain: &mut MessageChain,
-    mut data: HashMap<String, String>,
+    mut data: BTreeMap<String, String>,
     contact: Contact,
 ) -> RCResult<()> {
     let cover_url = data.remove("cover").unwrap_or_default();
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use xml::reader::XmlEvent;
 
@@ -31,7 +31,7 @@ pub fn xml_to_proto(s: String) -> Vec<pbbot::Message> {
             XmlEvent::Characters(s) => {
                 output.push(pbbot::Message {
                     r#type: "text".into(),
-                    data: HashMap::from([("text".to_string(), s)]),
+                    data: BTreeMap::from([("text".to_string(), s)]),
                 });
             }
             _ => continue,
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use ricq::msg::elem::RQElem;
 use ricq::msg::{elem, MessageChain};
+use ricq::structs::ForwardMessage;
 use ricq::Client;
 
 use crate::idl::pbbot;
@@ -16,6 +17,15 @@ pub fn to_proto_chain(_client: &Arc<Client>, message: MessageChain) -> Vec<pbbot
             RQElem::Face(element) => append_face(&mut chain, element),
             RQElem::FriendImage(element) => append_friend_image(&mut chain, element),
             RQElem::GroupImage(element) => append_group_image(&mut chain, element),
+            RQElem::RichMsg(element) if element.service_id == 35 => {
+                append_forward(&mut chain, element)
+            }
+            RQElem::RichMsg(element) => append_xml(&mut chain, element),
+            RQElem::LightApp(element) => append_json(&mut chain, element),
+            RQElem::Reply(element) => append_reply(&mut chain, element),
+            RQElem::Anonymous(_) => {
+                // 在 GroupMessageEvent.anonymous 中
+            }
             RQElem::Other(element) => {
                 tracing::trace!("other elem {:?}", element)
             }
@@ -30,14 +40,14 @@ pub fn to_proto_chain(_client: &Arc<Client>, message: MessageChain) -> Vec<pbbot
 pub fn append_text(chain: &mut Vec<pbbot::Message>, element: elem::Text) {
     chain.push(pbbot::Message {
         r#type: "text".into(),
-        data: HashMap::from([("text".into(), element.content)]),
+        data: BTreeMap::from([("text".into(), element.content)]),
     })
 }
 
 pub fn append_at(chain: &mut Vec<pbbot::Message>, element: elem::At) {
     chain.push(pbbot::Message {
         r#type: "at".into(),
-        data: HashMap::from([(
+        data: BTreeMap::from([(
             "qq".into(),
             if element.target != 0 {
                 element.target.to_string()
@@ -51,20 +61,127 @@ pub fn append_at(chain: &mut Vec<pbbot::Message>, element: elem::At) {
 pub fn append_face(chain: &mut Vec<pbbot::Message>, element: elem::Face) {
     chain.push(pbbot::Message {
         r#type: "face".into(),
-        data: HashMap::from([("id".into(), element.index.to_string())]),
+        data: BTreeMap::from([("id".into(), element.index.to_string())]),
     })
 }
 
 pub fn append_friend_image(chain: &mut Vec<pbbot::Message>, element: elem::FriendImage) {
     chain.push(pbbot::Message {
         r#type: "image".into(),
-        data: HashMap::from([("url".into(), element.url())]),
+        data: BTreeMap::from([("url".into(), element.url())]),
     })
 }
 
 pub fn append_group_image(chain: &mut Vec<pbbot::Message>, element: elem::GroupImage) {
     chain.push(pbbot::Message {
         r#type: "image".into(),
-        data: HashMap::from([("url".into(), element.url())]),
+        data: BTreeMap::from([("url".into(), element.url())]),
+    })
+}
+
+// user_id、seq、time 加上事件的 group_id 可组成 MessageReceipt 用于 GetMsgReq
+pub fn append_reply(chain: &mut Vec<pbbot::Message>, element: elem::Reply) {
+    chain.push(pbbot::Message {
+        r#type: "reply".into(),
+        data: BTreeMap::from([
+            ("user_id".into(), element.sender.to_string()),
+            ("seq".into(), element.reply_seq.to_string()),
+            ("time".into(), element.time.to_string()),
+        ]),
+    })
+}
+
+// 群消息中的回复补上 group_id
+pub fn set_reply_group_id(chain: &mut [pbbot::Message], group_id: i64) {
+    for element in chain.iter_mut().filter(|e| e.r#type == "reply") {
+        element.data.insert("group_id".into(), group_id.to_string());
+    }
+}
+
+// 语音消息单独收到，url 由 GroupAudioMessage/FriendAudioMessage 获取
+pub fn append_record(chain: &mut Vec<pbbot::Message>, url: String) {
+    chain.push(pbbot::Message {
+        r#type: "record".into(),
+        data: BTreeMap::from([("url".into(), url)]),
+    })
+}
+
+// 小程序卡片，data 为 json
+pub fn append_json(chain: &mut Vec<pbbot::Message>, element: elem::LightApp) {
+    chain.push(pbbot::Message {
+        r#type: "json".into(),
+        data: BTreeMap::from([("data".into(), element.content)]),
     })
 }
+
+// xml 卡片，合并转发（service_id 35）除外
+pub fn append_xml(chain: &mut Vec<pbbot::Message>, element: elem::RichMsg) {
+    chain.push(pbbot::Message {
+        r#type: "xml".into(),
+        data: BTreeMap::from([
+            ("data".into(), element.template1),
+            ("service_id".into(), element.service_id.to_string()),
+        ]),
+    })
+}
+
+// 合并转发，id 为 m_resid，通过 GetForwardMsgReq 获取内容
+pub fn append_forward(chain: &mut Vec<pbbot::Message>, element: elem::RichMsg) {
+    match forward_res_id(&element.template1) {
+        Some(id) => chain.push(pbbot::Message {
+            r#type: "forward".into(),
+            data: BTreeMap::from([("id".into(), id)]),
+        }),
+        None => tracing::warn!("forward without m_resid {:?}", element),
+    }
+}
+
+fn forward_res_id(template: &str) -> Option<String> {
+    let start = template.find("m_resid=\"")? + "m_resid=\"".len();
+    let end = template[start..].find('"')? + start;
+    Some(template[start..end].to_string())
+}
+
+pub fn to_proto_forward(client: &Arc<Client>, nodes: Vec<ForwardMessage>) -> pbbot::ForwardMessage {
+    let mut data = BTreeMap::new();
+    let chain = to_proto_forward_chain(client, nodes, &mut data);
+    pbbot::ForwardMessage {
+        chain: Some(chain),
+        data,
+    }
+}
+
+// 嵌套的转发保存在 data 中，forward_node 为 data 的 key
+fn to_proto_forward_chain(
+    client: &Arc<Client>,
+    nodes: Vec<ForwardMessage>,
+    data: &mut BTreeMap<i32, pbbot::ForwardChain>,
+) -> pbbot::ForwardChain {
+    let mut content = Vec::new();
+    for node in nodes {
+        content.push(match node {
+            ForwardMessage::Message(node) => pbbot::ForwardContent {
+                sender_id: node.sender_id,
+                time: node.time,
+                sender_name: node.sender_name,
+                content: Some(pbbot::forward_content::Content::MessageNode(
+                    pbbot::ForwardMessageNode {
+                        message: to_proto_chain(client, node.elements),
+                    },
+                )),
+            },
+            ForwardMessage::Forward(node) => {
+                let chain = to_proto_forward_chain(client, node.nodes, data);
+                let index = data.len() as i32;
+                data.insert(index, chain);
+                pbbot::ForwardContent {
+                    sender_id: node.sender_id,
+                    time: node.time,
+                    sender_name: node.sender_name,
+                    content: Some(pbbot::forward_content::Content::ForwardNode(index)),
+                }
+            }
+        });
+    }
+    pbbot::ForwardChain { content }
+}
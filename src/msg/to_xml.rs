@@ -21,7 +21,7 @@ pub fn proto_to_xml(s: Vec<pbbot::Message>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
 
     use crate::idl::pbbot;
     use crate::msg::to_xml::proto_to_xml;
@@ -31,11 +31,11 @@ mod tests {
         let p = vec![
             pbbot::Message {
                 r#type: "text".into(),
-                data: HashMap::from([("text".to_string(), "xxx".to_string())]),
+                data: BTreeMap::from([("text".to_string(), "xxx".to_string())]),
             },
             pbbot::Message {
                 r#type: "at".into(),
-                data: HashMap::from([("qq".to_string(), "123".to_string())]),
+                data: BTreeMap::from([("qq".to_string(), "123".to_string())]),
             },
         ];
         let x = proto_to_xml(p);
@@ -1,22 +1,31 @@
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use futures::{SinkExt, StreamExt};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::task::JoinHandle;
 use tokio_tungstenite::tungstenite::http::{Request, Uri};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::tungstenite::Message;
 
-use crate::api_handler::handle_api_frame;
+use crate::api_handler::{handle_api_frame, SUPPORTED_APIS};
+use crate::bot::bots::{bot_ids, get_bot};
 use crate::bot::Bot;
 use crate::error::{RCError, RCResult};
+use crate::event::{event_frame_type, is_message_sent, to_proto_heartbeat, to_proto_lifecycle};
+use crate::handler::ConvertU8;
 use crate::idl::pbbot;
-use crate::idl::pbbot::frame::Data;
+use crate::idl::pbbot::frame::{Data, FrameType};
 
 use super::pb_to_bytes::PbToBytes;
+use super::script::ScriptPlugin;
+use super::sign::{has_unknown_fields, sign_frame, verify_frame};
 use super::Plugin;
 
 pub struct PluginConnection {
@@ -24,6 +33,8 @@ pub struct PluginConnection {
     url_index: AtomicU32,
     out_channel: broadcast::Sender<Message>,
     pub stop_channel: broadcast::Sender<()>,
+    stopped: AtomicBool,
+    join_handle: Mutex<Option<JoinHandle<()>>>,
     event_seq: AtomicU32,
 }
 
@@ -38,6 +49,8 @@ impl PluginConnection {
             plugin,
             out_channel,
             stop_channel,
+            stopped: AtomicBool::new(false),
+            join_handle: Mutex::new(None),
             event_seq: AtomicU32::new(0),
         }
     }
@@ -46,11 +59,60 @@ impl PluginConnection {
         self.out_channel.send(msg).ok();
     }
 
+    pub fn send_frame(&self, mut frame: pbbot::Frame) {
+        self.sign(&mut frame);
+        self.send_msg(Message::Binary(frame.to_bytes()));
+    }
+
+    fn sign(&self, frame: &mut pbbot::Frame) {
+        if !self.plugin.secret.is_empty() {
+            sign_frame(&self.plugin.secret, frame);
+        }
+    }
+
+    // 断开后自动重连，直到 stop
+    pub fn spawn(self: &Arc<Self>, bot: Option<Arc<Bot>>) {
+        let name = self.plugin.name.clone();
+        let plugin = self.clone();
+        let handle = tokio::spawn(async move {
+            let mut stop_signal = plugin.stop_channel.subscribe();
+            loop {
+                // 阻塞到断开，stop 时由 start 发送 close frame 后返回
+                let reason = plugin.start(bot.as_ref()).await;
+                if plugin.stopped.load(Ordering::Relaxed) {
+                    tracing::info!("plugin [{}] stopped", name);
+                    break;
+                }
+                tracing::warn!("plugin [{}] error: {:?}", name, reason);
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                    _ = stop_signal.recv() => {
+                        break;
+                    }
+                }
+            }
+        });
+        *self.join_handle.lock().unwrap() = Some(handle);
+    }
+
     pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
         self.stop_channel.send(()).ok();
     }
 
-    pub async fn start(self: &Arc<Self>, bot: &Arc<Bot>) -> RCResult<()> {
+    // 等待 stop 后连接关闭
+    pub async fn wait_stopped(&self) {
+        let handle = self.join_handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            handle.await.ok();
+        }
+    }
+
+    // bot 为 None 时所有机器人共用该连接，API 请求按 bot_id 路由
+    pub async fn start(self: &Arc<Self>, bot: Option<&Arc<Bot>>) -> RCResult<()> {
+        if let Some(script) = self.plugin.script.as_ref() {
+            return self.start_script(bot, script).await;
+        }
         let url_index = self.url_index.fetch_add(1, Ordering::Relaxed);
         let uri: Uri = self
             .plugin
@@ -75,17 +137,34 @@ impl PluginConnection {
         .map_err(tokio::io::Error::from)
         .flatten()?;
         tracing::info!("succeed to connect plugin [{}]", self.plugin.name);
-        let req = Request::builder()
-            .uri(uri)
-            .header("x-self-id", bot.client.uin().await)
-            .body(())
-            .map_err(RCError::TungsteniteHttp)?;
+        let mut req = Request::builder().uri(uri);
+        if let Some(bot) = bot {
+            req = req.header("x-self-id", bot.client.uin().await);
+        }
+        let req = req.body(()).map_err(RCError::TungsteniteHttp)?;
         let (stream, _) = tokio_tungstenite::client_async(req, stream)
             .await
             .map_err(RCError::WS)?;
         let (mut w, mut r) = stream.split();
+        let mut hello = self.hello_frame(bot).await;
+        self.sign(&mut hello);
+        w.send(Message::Binary(hello.to_bytes()))
+            .await
+            .map_err(RCError::WS)?;
+        for mut frame in self.lifecycle_connect_frames(bot).await {
+            self.sign(&mut frame);
+            w.send(Message::Binary(frame.to_bytes()))
+                .await
+                .map_err(RCError::WS)?;
+        }
         let mut out_channel = self.out_channel.subscribe();
         let mut stop_channel = self.stop_channel.subscribe();
+        if self.stopped.load(Ordering::Relaxed) {
+            w.send(close_message()).await.ok();
+            return Err(RCError::Other("plugin is stopped".into()));
+        }
+        let heartbeat_interval = Duration::from_secs(self.plugin.heartbeat_interval);
+        let mut heartbeat = tokio::time::interval(heartbeat_interval.max(Duration::from_secs(1)));
 
         let name = self.plugin.name.clone();
         loop {
@@ -94,6 +173,14 @@ impl PluginConnection {
                     tracing::trace!("plugin send ping {}", name);
                     self.send_msg(Message::Ping("ping".as_bytes().to_vec()));
                 }
+                _ = heartbeat.tick(), if !heartbeat_interval.is_zero() => {
+                    for bot in connection_bots(bot) {
+                        let self_id = bot.client.uin().await;
+                        let online = bot.online.load(Ordering::Relaxed);
+                        let event = to_proto_heartbeat(self_id, heartbeat_interval, online);
+                        self.handle_event(self_id, Data::HeartbeatMetaEvent(event)).await;
+                    }
+                }
                 out_message = out_channel.recv() => {
                     w.send(out_message.map_err(|e|RCError::Other(format!("failed to recv out_message {}",e)))?).await.map_err(RCError::WS)?;
                 }
@@ -101,13 +188,30 @@ impl PluginConnection {
                     let msg=in_message.ok_or_else(||RCError::Other("failed to recv ws in_message".into()))??;
                     match msg{
                         Message::Binary(m) => {
-                            let b=bot.clone();
+                            let b=bot.cloned();
                             let conn=self.clone();
                                 let _: JoinHandle<Result<(),RCError>> =tokio::spawn(async move {
                                     let req = pbbot::Frame::from_bytes(&m).map_err(RCError::PB)?;
+                                    if !conn.plugin.secret.is_empty() && !verify_frame(&conn.plugin.secret, &req) {
+                                        tracing::warn!("plugin [{}] api frame signature mismatch", conn.plugin.name);
+                                        let error = if has_unknown_fields(&m, &req) {
+                                            "invalid signature: frame contains unknown fields"
+                                        } else {
+                                            "invalid signature"
+                                        };
+                                        conn.send_frame(error_frame(req, error));
+                                        return Ok(());
+                                    }
+                                    let b = match b.or_else(|| get_bot(req.bot_id)) {
+                                        Some(b) => b,
+                                        None => {
+                                            conn.send_frame(error_frame(req, "bot not found"));
+                                            return Ok(());
+                                        }
+                                    };
                                     // TODO check api permission
-                                    let resp = handle_api_frame(&b,req).await;
-                                    conn.send_msg(Message::Binary(resp.to_bytes()));
+                                    let resp = handle_api_frame(&b, req, &conn.plugin.name).await;
+                                    conn.send_frame(resp);
                                     Ok(())
                                 });
                             }
@@ -121,43 +225,167 @@ impl PluginConnection {
                         }
                 }
                 _ = stop_channel.recv() => {
+                    w.send(close_message()).await.ok();
                     return Err(RCError::Other("plugin is stopped".into()))
                 }
             }
         }
     }
 
-    pub async fn handle_event(&self, bot_id: i64, event: pbbot::frame::Data) {
-        // TODO frame_type
-        // TODO filter event type
-        let frame = pbbot::Frame {
-            bot_id,
-            frame_type: match event {
-                Data::PrivateMessageEvent(_) => pbbot::frame::FrameType::TPrivateMessageEvent,
-                Data::GroupMessageEvent(_) => pbbot::frame::FrameType::TGroupMessageEvent,
-                Data::GroupUploadNoticeEvent(_) => pbbot::frame::FrameType::TGroupUploadNoticeEvent,
-                Data::GroupAdminNoticeEvent(_) => pbbot::frame::FrameType::TGroupAdminNoticeEvent,
-                Data::GroupDecreaseNoticeEvent(_) => {
-                    pbbot::frame::FrameType::TGroupDecreaseNoticeEvent
+    // 脚本插件，从 out_channel 读取事件交给脚本处理
+    async fn start_script(&self, bot: Option<&Arc<Bot>>, path: &str) -> RCResult<()> {
+        let mut script = ScriptPlugin::load(&self.plugin.name, path)?;
+        tracing::info!("succeed to load script plugin [{}]", self.plugin.name);
+        let mut out_channel = self.out_channel.subscribe();
+        let mut stop_channel = self.stop_channel.subscribe();
+        if self.stopped.load(Ordering::Relaxed) {
+            return Err(RCError::Other("plugin is stopped".into()));
+        }
+        let mut reload_interval = tokio::time::interval(Duration::from_secs(2));
+        loop {
+            tokio::select! {
+                _ = reload_interval.tick() => {
+                    script.reload_if_modified();
                 }
-                Data::GroupIncreaseNoticeEvent(_) => {
-                    pbbot::frame::FrameType::TGroupIncreaseNoticeEvent
+                out_message = out_channel.recv() => {
+                    let frame = match out_message {
+                        Ok(Message::Binary(b)) => pbbot::Frame::from_bytes(&b).map_err(RCError::PB)?,
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(n)) => {
+                            tracing::warn!("script plugin [{}] lagged {} events", self.plugin.name, n);
+                            continue;
+                        }
+                        Err(e) => return Err(RCError::Other(format!("failed to recv out_message {}", e))),
+                    };
+                    let bot = match bot.cloned().or_else(|| get_bot(frame.bot_id)) {
+                        Some(bot) => bot,
+                        None => continue,
+                    };
+                    if let Some(data) = frame.data {
+                        // 脚本中的 API 同步执行，不能阻塞 runtime
+                        script = tokio::task::spawn_blocking(move || {
+                            script.on_event(bot, data);
+                            script
+                        })
+                        .await
+                        .map_err(|e| RCError::Other(format!("script task error: {}", e)))?;
+                    }
                 }
-                Data::GroupBanNoticeEvent(_) => pbbot::frame::FrameType::TGroupBanNoticeEvent,
-                Data::FriendAddNoticeEvent(_) => pbbot::frame::FrameType::TFriendAddNoticeEvent,
-                Data::GroupRecallNoticeEvent(_) => pbbot::frame::FrameType::TGroupRecallNoticeEvent,
-                Data::FriendRecallNoticeEvent(_) => {
-                    pbbot::frame::FrameType::TFriendRecallNoticeEvent
+                _ = stop_channel.recv() => {
+                    return Err(RCError::Other("plugin is stopped".into()))
                 }
-                Data::FriendRequestEvent(_) => pbbot::frame::FrameType::TFriendRequestEvent,
-                Data::GroupRequestEvent(_) => pbbot::frame::FrameType::TGroupRequestEvent,
-                _ => pbbot::frame::FrameType::Tunknown,
-            } as i32,
+            }
+        }
+    }
+
+    // 连接成功后告知插件版本、支持的 API 和事件过滤
+    async fn hello_frame(&self, bot: Option<&Arc<Bot>>) -> pbbot::Frame {
+        let (self_id, protocol, nickname) = match bot {
+            Some(bot) => (
+                bot.client.uin().await,
+                bot.client.version().await.protocol.to_u8() as i32,
+                bot.client.account_info.read().await.nickname.clone(),
+            ),
+            None => (0, 0, "".into()),
+        };
+        pbbot::Frame {
+            bot_id: self_id,
+            frame_type: FrameType::THelloMetaEvent as i32,
+            echo: self.event_seq.fetch_add(1, Ordering::Relaxed).to_string(),
+            ok: true,
+            data: Some(Data::HelloMetaEvent(pbbot::HelloMetaEvent {
+                time: chrono::Utc::now().timestamp(),
+                self_id,
+                post_type: "meta_event".into(),
+                meta_event_type: "hello".into(),
+                app_version: env!("CARGO_PKG_VERSION").into(),
+                protocol,
+                nickname,
+                supported_apis: SUPPORTED_APIS.iter().map(|t| *t as i32).collect(),
+                event_filter: self.plugin.event_filter.clone(),
+                encoding: "protobuf".into(),
+                bot_ids: match bot {
+                    Some(_) => vec![self_id],
+                    None => bot_ids(),
+                },
+                extra: Default::default(),
+            })),
+            extra: Default::default(),
+        }
+    }
+
+    // 连接成功时推送 lifecycle connect，共用连接每个机器人推送一次
+    async fn lifecycle_connect_frames(&self, bot: Option<&Arc<Bot>>) -> Vec<pbbot::Frame> {
+        let mut frames = Vec::new();
+        for bot in connection_bots(bot) {
+            frames.push(self.lifecycle_connect_frame(bot.client.uin().await));
+        }
+        frames
+    }
+
+    fn lifecycle_connect_frame(&self, self_id: i64) -> pbbot::Frame {
+        pbbot::Frame {
+            bot_id: self_id,
+            frame_type: FrameType::TLifecycleMetaEvent as i32,
+            echo: self.event_seq.fetch_add(1, Ordering::Relaxed).to_string(),
+            ok: true,
+            data: Some(Data::LifecycleMetaEvent(to_proto_lifecycle(
+                self_id, "connect", "",
+            ))),
+            extra: Default::default(),
+        }
+    }
+
+    // 共用连接建立后登录的机器人，重新推送 hello（bot_ids 包含新机器人）和该机器人的 lifecycle connect
+    pub async fn announce_bot(&self, bot: &Arc<Bot>) {
+        let hello = self.hello_frame(None).await;
+        self.send_frame(hello);
+        let frame = self.lifecycle_connect_frame(bot.client.uin().await);
+        self.send_frame(frame);
+    }
+
+    pub async fn handle_event(&self, bot_id: i64, event: pbbot::frame::Data) {
+        let frame_type = event_frame_type(&event) as i32;
+        if !self.plugin.event_filter.is_empty() && !self.plugin.event_filter.contains(&frame_type) {
+            return;
+        }
+        if !self.plugin.report_self_message && is_message_sent(&event) {
+            return;
+        }
+        let frame = pbbot::Frame {
+            bot_id,
+            frame_type,
             echo: self.event_seq.fetch_add(1, Ordering::Relaxed).to_string(),
             ok: true,
             data: Some(event),
             extra: Default::default(),
         };
-        self.send_msg(Message::Binary(frame.to_bytes()));
+        self.send_frame(frame);
+    }
+}
+
+// bot 为 None 时为共用连接，返回所有机器人
+fn connection_bots(bot: Option<&Arc<Bot>>) -> Vec<Arc<Bot>> {
+    match bot {
+        Some(bot) => vec![bot.clone()],
+        None => bot_ids().into_iter().filter_map(get_bot).collect(),
+    }
+}
+
+fn close_message() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "pbrq is stopping".into(),
+    }))
+}
+
+fn error_frame(req: pbbot::Frame, error: &str) -> pbbot::Frame {
+    pbbot::Frame {
+        bot_id: req.bot_id,
+        frame_type: req.frame_type + 100,
+        echo: req.echo,
+        ok: false,
+        data: None,
+        extra: BTreeMap::from([("error".into(), error.into())]),
     }
 }
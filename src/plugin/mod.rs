@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 
 pub mod conn;
 pub mod pb_to_bytes;
+pub mod script;
+pub mod sign;
 pub mod storage;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -11,8 +13,19 @@ pub struct Plugin {
     pub name: String,
     pub disabled: bool,
     pub urls: Vec<String>,
+    // 脚本插件路径，相对路径基于 plugins 目录，设置后不再连接 urls
+    pub script: Option<String>,
+    // 事件过滤，FrameType，为空时推送全部事件
+    pub event_filter: Vec<i32>,
+    // 签名密钥，设置后发送的 frame 带签名，收到的 API 请求必须带签名
+    pub secret: String,
+    // 所有机器人共用一个连接，事件通过 bot_id 区分，修改后重启生效
+    pub shared: bool,
+    // 心跳间隔，秒，为 0 时不发送
+    pub heartbeat_interval: u64,
+    // 推送机器人自己发送的消息，post_type 为 message_sent，pbrq 发送的消息 extra.origin 为调用的插件
+    pub report_self_message: bool,
     // TODO
-    // 	EventFilter  []int32             `json:"event_filter"`  // 事件过滤
     // 	ApiFilter    []int32             `json:"api_filter"`    // API过滤
     // 	RegexFilter  string              `json:"regex_filter"`  // 正则过滤
     // 	RegexReplace string              `json:"regex_replace"` // 正则替换
@@ -25,6 +38,12 @@ impl Default for Plugin {
             name: "default".to_string(),
             disabled: false,
             urls: vec!["ws://localhost:8081/ws/rq/".into()],
+            script: None,
+            event_filter: Vec::new(),
+            secret: "".into(),
+            shared: false,
+            heartbeat_interval: 0,
+            report_self_message: false,
         }
     }
 }
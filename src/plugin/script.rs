@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use tokio::runtime::Handle;
+
+use crate::api_handler::call_api;
+use crate::bot::Bot;
+use crate::error::{RCError, RCResult};
+use crate::idl::pbbot;
+use crate::idl::pbbot::frame::Data;
+use crate::idl::pbbot::MessageReceipt;
+use crate::plugin::storage::PLUGIN_PATH;
+
+// 单次 on_event 最多执行的操作数和调用深度，防止死循环卡住线程
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+
+// rhai 脚本插件，脚本中定义 fn on_event(event) 处理事件
+pub struct ScriptPlugin {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    modified: Option<SystemTime>,
+    // 正在处理事件的机器人，脚本调用 API 时使用
+    bot: Arc<Mutex<Option<Arc<Bot>>>>,
+}
+
+impl ScriptPlugin {
+    // 需要在 tokio runtime 中调用，脚本中的 API 通过当前 runtime 执行
+    pub fn load(name: &str, path: &str) -> RCResult<Self> {
+        let path = Path::new(PLUGIN_PATH).join(path);
+        let bot = Arc::new(Mutex::new(None));
+        let engine = new_engine(ApiCaller {
+            origin: name.to_string(),
+            bot: bot.clone(),
+            runtime: Handle::current(),
+        });
+        let ast = engine.compile(std::fs::read_to_string(&path)?)?;
+        Ok(Self {
+            modified: modified_time(&path),
+            path,
+            engine,
+            ast,
+            bot,
+        })
+    }
+
+    // 文件修改后重新编译，编译失败继续使用旧脚本
+    pub fn reload_if_modified(&mut self) {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match std::fs::read_to_string(&self.path)
+            .map_err(RCError::IO)
+            .and_then(|s| self.engine.compile(s).map_err(RCError::ScriptParse))
+        {
+            Ok(ast) => {
+                tracing::info!("succeed to reload script {}", self.path.display());
+                self.ast = ast;
+            }
+            Err(err) => tracing::error!("failed to reload script {}: {}", self.path.display(), err),
+        }
+    }
+
+    // 调用脚本 on_event，脚本中的 API 同步执行，需要在 spawn_blocking 中调用
+    pub fn on_event(&self, bot: Arc<Bot>, event: Data) {
+        let event = match data_to_dynamic(event) {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::error!("failed to convert event to script: {}", err);
+                return;
+            }
+        };
+        *self.bot.lock().unwrap() = Some(bot);
+        if let Err(err) =
+            self.engine
+                .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "on_event", (event,))
+        {
+            tracing::error!("script {} on_event error: {}", self.path.display(), err);
+        }
+        *self.bot.lock().unwrap() = None;
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// 事件和 API 返回转换为脚本中的 map，去掉 oneof 外层
+fn data_to_dynamic(data: Data) -> RCResult<Dynamic> {
+    let value = match serde_json::to_value(data) {
+        Ok(serde_json::Value::Object(m)) => m.into_iter().next().map(|(_, v)| v),
+        Ok(_) => None,
+        Err(err) => return Err(RCError::Other(err.to_string())),
+    }
+    .ok_or(RCError::None("event"))?;
+    rhai::serde::to_dynamic(value).map_err(|err| RCError::Other(err.to_string()))
+}
+
+fn text_message(text: &str) -> Vec<pbbot::Message> {
+    vec![pbbot::Message {
+        r#type: "text".into(),
+        data: BTreeMap::from([("text".into(), text.into())]),
+    }]
+}
+
+// 脚本中的 message_id 转回 MessageReceipt
+fn map_to_receipt(map: &Map) -> MessageReceipt {
+    let int = |key: &str| {
+        map.get(key)
+            .and_then(|v| v.as_int().ok())
+            .unwrap_or_default()
+    };
+    let ints = |key: &str| -> Vec<i32> {
+        map.get(key)
+            .and_then(|v| v.clone().try_cast::<Array>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| v.as_int().ok().map(|i| i as i32))
+            .collect()
+    };
+    MessageReceipt {
+        sender_id: int("sender_id"),
+        time: int("time"),
+        seqs: ints("seqs"),
+        rands: ints("rands"),
+        group_id: int("group_id"),
+    }
+}
+
+#[derive(Clone)]
+struct ApiCaller {
+    origin: String,
+    bot: Arc<Mutex<Option<Arc<Bot>>>>,
+    runtime: Handle,
+}
+
+impl ApiCaller {
+    // 返回 API 结果，失败时返回 ()
+    fn call(&self, req: Data) -> Dynamic {
+        let bot = match self.bot.lock().unwrap().clone() {
+            Some(bot) => bot,
+            None => return Dynamic::UNIT,
+        };
+        match self.runtime.block_on(call_api(&bot, req, &self.origin)) {
+            Ok(resp) => data_to_dynamic(resp).unwrap_or(Dynamic::UNIT),
+            Err(err) => {
+                tracing::warn!("script [{}] api error: {}", self.origin, err);
+                Dynamic::UNIT
+            }
+        }
+    }
+}
+
+fn new_engine(api: ApiCaller) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.on_print(|s| tracing::info!("script: {}", s));
+    let caller = api.clone();
+    engine.register_fn("send_group_msg", move |group_id: i64, message: &str| {
+        caller.call(Data::SendGroupMsgReq(pbbot::SendGroupMsgReq {
+            group_id,
+            message: text_message(message),
+            auto_escape: false,
+        }))
+    });
+    let caller = api.clone();
+    engine.register_fn("send_private_msg", move |user_id: i64, message: &str| {
+        caller.call(Data::SendPrivateMsgReq(pbbot::SendPrivateMsgReq {
+            user_id,
+            message: text_message(message),
+            auto_escape: false,
+            group_id: 0,
+        }))
+    });
+    let caller = api.clone();
+    engine.register_fn("delete_msg", move |message_id: Map| {
+        caller.call(Data::DeleteMsgReq(pbbot::DeleteMsgReq {
+            message_id: Some(map_to_receipt(&message_id)),
+        }))
+    });
+    let caller = api.clone();
+    engine.register_fn(
+        "set_group_ban",
+        move |group_id: i64, user_id: i64, duration: i64| {
+            caller.call(Data::SetGroupBanReq(pbbot::SetGroupBanReq {
+                group_id,
+                user_id,
+                duration: duration as i32,
+            }))
+        },
+    );
+    let caller = api.clone();
+    engine.register_fn(
+        "set_group_kick",
+        move |group_id: i64, user_id: i64, reject_add_request: bool| {
+            caller.call(Data::SetGroupKickReq(pbbot::SetGroupKickReq {
+                group_id,
+                user_id,
+                reject_add_request,
+                user_ids: Vec::new(),
+            }))
+        },
+    );
+    let caller = api;
+    engine.register_fn(
+        "get_group_member_info",
+        move |group_id: i64, user_id: i64| {
+            caller.call(Data::GetGroupMemberInfoReq(pbbot::GetGroupMemberInfoReq {
+                group_id,
+                user_id,
+                no_cache: false,
+            }))
+        },
+    );
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_operations() {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine.compile("fn on_event(e) { loop {} }").unwrap();
+        let result =
+            engine.call_fn::<Dynamic>(&mut Scope::new(), &ast, "on_event", (Dynamic::UNIT,));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_map_to_receipt() {
+        let engine = Engine::new();
+        let map: Map = engine
+            .eval(r#"#{sender_id: 1, time: 2, seqs: [3], rands: [4, 5], group_id: 6}"#)
+            .unwrap();
+        let receipt = map_to_receipt(&map);
+        assert_eq!(receipt.sender_id, 1);
+        assert_eq!(receipt.seqs, vec![3]);
+        assert_eq!(receipt.rands, vec![4, 5]);
+        assert_eq!(receipt.group_id, 6);
+    }
+}
@@ -0,0 +1,135 @@
+use hmac::{Hmac, Mac};
+use prost::Message;
+use sha2::Sha256;
+
+use crate::idl::pbbot;
+
+use super::pb_to_bytes::PbToBytes;
+
+pub const SIGNATURE_KEY: &str = "signature";
+
+// HMAC-SHA256(secret, 清空 extra 后的 frame)，签名放在 extra 中所以 extra 不参与签名
+// map 为 BTreeMap，重新编码时按 key 排序，和插件的确定性编码一致
+fn frame_mac(secret: &str, frame: &pbbot::Frame) -> Hmac<Sha256> {
+    let mut unsigned = frame.clone();
+    unsigned.extra.clear();
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(&unsigned.to_bytes());
+    mac
+}
+
+pub fn sign_frame(secret: &str, frame: &mut pbbot::Frame) {
+    let signature = hex::encode(frame_mac(secret, frame).finalize().into_bytes());
+    frame.extra.insert(SIGNATURE_KEY.into(), signature);
+}
+
+pub fn verify_frame(secret: &str, frame: &pbbot::Frame) -> bool {
+    frame
+        .extra
+        .get(SIGNATURE_KEY)
+        .and_then(|s| hex::decode(s).ok())
+        .map(|signature| frame_mac(secret, frame).verify_slice(&signature).is_ok())
+        .unwrap_or(false)
+}
+
+// 解码时丢弃未知字段（例如更新的 IDL），重新编码后比原始数据短，无法验证签名
+pub fn has_unknown_fields(raw: &[u8], frame: &pbbot::Frame) -> bool {
+    frame.encoded_len() < raw.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_frame() {
+        let mut frame = pbbot::Frame {
+            bot_id: 123,
+            frame_type: pbbot::frame::FrameType::TGetLoginInfoReq as i32,
+            echo: "1".into(),
+            data: Some(pbbot::frame::Data::GetLoginInfoReq(
+                pbbot::GetLoginInfoReq {},
+            )),
+            ..Default::default()
+        };
+        assert!(!verify_frame("secret", &frame));
+        sign_frame("secret", &mut frame);
+        assert!(verify_frame("secret", &frame));
+        assert!(!verify_frame("other", &frame));
+        frame.extra.insert("foo".into(), "bar".into());
+        assert!(verify_frame("secret", &frame));
+        frame.echo = "2".into();
+        assert!(!verify_frame("secret", &frame));
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let mut frame = pbbot::Frame {
+            bot_id: 123,
+            frame_type: pbbot::frame::FrameType::TGetLoginInfoReq as i32,
+            echo: "1".into(),
+            data: Some(pbbot::frame::Data::GetLoginInfoReq(
+                pbbot::GetLoginInfoReq {},
+            )),
+            ..Default::default()
+        };
+        // 插件签名时包含字段 1000（varint 1）
+        let unknown = [0xc0, 0x3e, 0x01];
+        let mut unsigned = frame.to_bytes();
+        unsigned.extend(unknown);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(&unsigned);
+        frame.extra.insert(
+            SIGNATURE_KEY.into(),
+            hex::encode(mac.finalize().into_bytes()),
+        );
+        let mut raw = frame.to_bytes();
+        assert!(!has_unknown_fields(&raw, &frame));
+        raw.extend(unknown);
+        let decoded = pbbot::Frame::from_bytes(&raw).unwrap();
+        assert!(has_unknown_fields(&raw, &decoded));
+        assert!(!verify_frame("secret", &decoded));
+    }
+
+    #[test]
+    fn test_sign_frame_with_map() {
+        let message = |data: &[(&str, &str)]| pbbot::Message {
+            r#type: "image".into(),
+            data: data
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let frame = |data: &[(&str, &str)]| pbbot::Frame {
+            bot_id: 123,
+            frame_type: pbbot::frame::FrameType::TSendGroupMsgReq as i32,
+            echo: "1".into(),
+            data: Some(pbbot::frame::Data::SendGroupMsgReq(
+                pbbot::SendGroupMsgReq {
+                    group_id: 456,
+                    message: vec![message(data)],
+                    auto_escape: false,
+                },
+            )),
+            ..Default::default()
+        };
+        let mut signed = frame(&[("url", "http://a"), ("type", "flash"), ("file", "a.jpg")]);
+        sign_frame("secret", &mut signed);
+
+        // 插件发来的 bytes 解码后验证
+        let decoded = pbbot::Frame::from_bytes(&signed.to_bytes()).unwrap();
+        assert!(verify_frame("secret", &decoded));
+
+        // 插入顺序不同的 map 签名相同
+        let mut reordered = frame(&[("file", "a.jpg"), ("url", "http://a"), ("type", "flash")]);
+        sign_frame("secret", &mut reordered);
+        assert_eq!(signed.to_bytes(), reordered.to_bytes());
+
+        let mut tampered = decoded;
+        if let Some(pbbot::frame::Data::SendGroupMsgReq(req)) = tampered.data.as_mut() {
+            req.message[0].data.insert("url".into(), "http://b".into());
+        }
+        assert!(!verify_frame("secret", &tampered));
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::error::{RCError, RCResult};
+
+// silk 编码使用的采样率
+pub const SAMPLE_RATE: u32 = 24000;
+
+const SILK_HEADER: &[u8] = b"#!SILK_V3";
+const AMR_HEADER: &[u8] = b"#!AMR\n";
+// AMR-NB 12.2kbps 每帧 32 字节 20ms
+const AMR_FRAME_SIZE: usize = 32;
+
+pub struct Record {
+    pub data: Vec<u8>,
+    // 0: amr, 1: silk
+    pub codec: u32,
+    pub duration: Duration,
+}
+
+pub fn is_silk(data: &[u8]) -> bool {
+    data.starts_with(SILK_HEADER) || data.get(1..).map_or(false, |d| d.starts_with(SILK_HEADER))
+}
+
+pub fn is_amr(data: &[u8]) -> bool {
+    data.starts_with(AMR_HEADER)
+}
+
+// silk/amr 直接使用，其他格式解码后编码为 silk
+pub fn encode_record(data: Vec<u8>) -> RCResult<Record> {
+    if is_silk(&data) {
+        let pcm = decode_silk(&data)?;
+        return Ok(Record {
+            duration: pcm_duration(&pcm),
+            data,
+            codec: 1,
+        });
+    }
+    if is_amr(&data) {
+        return Ok(Record {
+            duration: Duration::from_millis(
+                ((data.len() - AMR_HEADER.len()) / AMR_FRAME_SIZE * 20) as u64,
+            ),
+            data,
+            codec: 0,
+        });
+    }
+    let pcm = decode_audio(data)?;
+    let duration = pcm_duration(&pcm);
+    let data = silk_rs::encode_silk(pcm, SAMPLE_RATE as i32, SAMPLE_RATE as i32, true)
+        .map_err(|e| RCError::Audio(format!("failed to encode silk: {:?}", e)))?;
+    Ok(Record {
+        data,
+        codec: 1,
+        duration,
+    })
+}
+
+// silk 解码为 pcm s16le 单声道
+pub fn decode_silk(data: &[u8]) -> RCResult<Vec<u8>> {
+    silk_rs::decode_silk(data, SAMPLE_RATE as i32)
+        .map_err(|e| RCError::Audio(format!("failed to decode silk: {:?}", e)))
+}
+
+// 解码 wav/mp3/ogg/flac/aac 等格式为 pcm s16le 单声道
+pub fn decode_audio(data: Vec<u8>) -> RCResult<Vec<u8>> {
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(
+            &Hint::new(),
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(symphonia_error)?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .ok_or_else(|| RCError::Audio("no audio track".into()))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(SAMPLE_RATE);
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(symphonia_error)?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(symphonia_error(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // 跳过损坏的帧
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(symphonia_error(e)),
+        };
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        let channels = spec.channels.count().max(1);
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        samples.extend(
+            buf.samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+    if samples.is_empty() {
+        return Err(RCError::Audio("no audio samples".into()));
+    }
+    Ok(resample(&samples, sample_rate, SAMPLE_RATE)
+        .into_iter()
+        .flat_map(|s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+        .collect())
+}
+
+fn symphonia_error(e: SymphoniaError) -> RCError {
+    RCError::Audio(format!("failed to decode audio: {}", e))
+}
+
+// 线性插值重采样
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let len = (samples.len() as u64 * to as u64 / from as u64) as usize;
+    let last = samples.len() - 1;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * from as f64 / to as f64;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = samples[index.min(last)];
+            let b = samples[(index + 1).min(last)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+fn pcm_duration(pcm: &[u8]) -> Duration {
+    Duration::from_millis(pcm.len() as u64 / 2 * 1000 / SAMPLE_RATE as u64)
+}
+
+// pcm s16le 单声道加上 wav 头
+pub fn pcm_to_wav(pcm: &[u8]) -> Vec<u8> {
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + pcm.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // 单声道
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 秒 440Hz 正弦波
+    fn sine_pcm() -> Vec<u8> {
+        (0..SAMPLE_RATE)
+            .flat_map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                (((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16).to_le_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_wav_record() {
+        let record = encode_record(pcm_to_wav(&sine_pcm())).unwrap();
+        assert_eq!(record.codec, 1);
+        assert!(is_silk(&record.data));
+        assert_eq!(record.duration, Duration::from_secs(1));
+
+        // silk 直接使用
+        let silk = encode_record(record.data.clone()).unwrap();
+        assert_eq!(silk.data, record.data);
+        assert!(!decode_silk(&silk.data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_encode_amr_record() {
+        let mut amr = AMR_HEADER.to_vec();
+        amr.extend(vec![0; AMR_FRAME_SIZE * 50]);
+        let record = encode_record(amr).unwrap();
+        assert_eq!(record.codec, 0);
+        assert_eq!(record.duration, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_resample() {
+        assert_eq!(resample(&[0.0, 1.0], 24000, 24000), vec![0.0, 1.0]);
+        assert_eq!(
+            resample(&[0.0, 1.0], 12000, 24000),
+            vec![0.0, 0.5, 1.0, 1.0]
+        );
+    }
+}
@@ -0,0 +1,676 @@
+use std::net::SocketAddr;
+
+use bytes::{Buf, BufMut, BytesMut};
+use prost::Message;
+use ricq::Client;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::bot::Bot;
+use crate::error::{RCError, RCResult};
+use crate::idl::pbbot::get_group_files_resp;
+
+pub const FILE_LIST_URL: &str = "https://pan.qun.qq.com/cgi-bin/group_file/get_file_list";
+pub const DELETE_FILE_URL: &str = "https://pan.qun.qq.com/cgi-bin/group_file/delete_file";
+pub const DOWNLOAD_URL: &str = "https://pan.qun.qq.com/cgi-bin/group_share_get_downurl";
+
+// 群文件消息为 elem_type 24 的 TransElemInfo
+pub const FILE_TRANS_ELEM_TYPE: i32 = 24;
+
+// 上传：0x6d6_0 申请上传，通过 highway exciting 通道上传数据，0x6d9_4 发送群文件消息
+const UPLOAD_FILE_CMD: &str = "OidbSvc.0x6d6_0";
+const FILE_FEED_CMD: &str = "OidbSvc.0x6d9_4";
+const GROUP_FILE_APP_ID: i32 = 3;
+const GROUP_FILE_BUS_ID: i32 = 102;
+const EXCITING_COMMAND_ID: i32 = 71;
+const EXCITING_CHUNK_SIZE: usize = 512 * 1024;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ObjMsg {
+    #[prost(uint32, optional, tag = "1")]
+    pub msg_type: Option<u32>,
+    #[prost(message, repeated, tag = "7")]
+    pub msg_content_info: Vec<MsgContentInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MsgContentInfo {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub content_info_id: Option<Vec<u8>>,
+    #[prost(message, optional, tag = "2")]
+    pub msg_file: Option<MsgFile>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MsgFile {
+    #[prost(uint32, optional, tag = "1")]
+    pub bus_id: Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub file_path: Option<Vec<u8>>,
+    #[prost(uint64, optional, tag = "3")]
+    pub file_size: Option<u64>,
+    #[prost(string, optional, tag = "4")]
+    pub file_name: Option<String>,
+}
+
+// elem_value: 0x01 + u16 长度 + ObjMsg
+pub fn decode_group_file(elem_value: &[u8]) -> Option<MsgFile> {
+    if elem_value.len() < 3 || elem_value[0] != 1 {
+        return None;
+    }
+    let len = u16::from_be_bytes([elem_value[1], elem_value[2]]) as usize;
+    let obj = ObjMsg::decode(elem_value.get(3..3 + len)?).ok()?;
+    obj.msg_content_info.into_iter().find_map(|c| c.msg_file)
+}
+
+pub async fn get_group_file_url(
+    bot: &Bot,
+    group_id: i64,
+    file_id: &str,
+    busid: i64,
+) -> RCResult<String> {
+    let resp = bot
+        .web_get(
+            DOWNLOAD_URL,
+            &[
+                ("uin", bot.client.uin().await.to_string()),
+                ("groupid", group_id.to_string()),
+                ("pa", format!("/{}{}", busid, file_id)),
+                ("r", rand::random::<u32>().to_string()),
+                ("charset", "utf-8".into()),
+                ("g_tk", bot.csrf_token().await.to_string()),
+            ],
+        )
+        .await?;
+    resp.pointer("/data/url")
+        .or_else(|| resp.get("url"))
+        .and_then(|u| u.as_str())
+        .map(ToString::to_string)
+        .ok_or_else(|| RCError::Other(format!("invalid group file url resp: {}", resp)))
+}
+
+// name 为空时从路径或 url 中取文件名，base64 没有文件名
+pub fn file_name_from_uri(uri: &str) -> Option<String> {
+    if uri.starts_with("base64://") {
+        return None;
+    }
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(ToString::to_string)
+}
+
+// 上传到群文件夹，返回 file_id 和 busid
+pub async fn upload_group_file(
+    bot: &Bot,
+    group_code: i64,
+    folder_id: &str,
+    name: &str,
+    data: Vec<u8>,
+) -> RCResult<(String, i64)> {
+    let client = &bot.client;
+    let md5 = md5::compute(&data).to_vec();
+    let sha1 = Sha1::digest(&data).to_vec();
+    let req = D6d6ReqBody {
+        upload_file_req: Some(UploadFileReqBody {
+            group_code,
+            app_id: GROUP_FILE_APP_ID,
+            bus_id: GROUP_FILE_BUS_ID,
+            entrance: 5,
+            parent_folder_id: folder_id.into(),
+            file_name: name.into(),
+            local_path: format!("/{}", name),
+            file_size: data.len() as i64,
+            sha: sha1.clone(),
+            md5: md5.clone(),
+            support_multi_upload: true,
+        }),
+    };
+    let rsp = D6d6RspBody::decode(
+        send_oidb(client, UPLOAD_FILE_CMD, 1750, 0, req.encode_to_vec()).await?,
+    )?
+    .upload_file_rsp
+    .ok_or(RCError::Other("empty upload file rsp".into()))?;
+    if rsp.ret_code != 0 {
+        return Err(RCError::Other(format!(
+            "upload group file failed: {} {}",
+            rsp.ret_code, rsp.client_wording
+        )));
+    }
+    // 服务器已有相同文件时不需要上传数据
+    if !rsp.bool_file_exist {
+        let ext = GroupFileUploadExt {
+            unknown1: 100,
+            unknown2: 1,
+            entry: Some(GroupFileUploadEntry {
+                busi_buff: Some(ExcitingBusiInfo {
+                    bus_id: rsp.bus_id,
+                    sender_uin: client.uin().await,
+                    receiver_uin: group_code,
+                    group_code,
+                }),
+                file_entry: Some(ExcitingFileEntry {
+                    file_size: data.len() as i64,
+                    md5: md5.clone(),
+                    sha1,
+                    file_id: rsp.file_id.clone().into_bytes(),
+                    upload_key: rsp.check_key.clone(),
+                }),
+                client_info: Some(ExcitingClientInfo {
+                    client_type: 2,
+                    app_id: client.version().await.app_id.to_string(),
+                    terminal_type: 2,
+                    client_ver: "9e9c09dc".into(),
+                    unknown: 4,
+                }),
+                file_name_info: Some(ExcitingFileNameInfo {
+                    file_name: name.into(),
+                }),
+                host: Some(ExcitingHostConfig {
+                    hosts: vec![ExcitingHostInfo {
+                        url: Some(ExcitingUrlInfo {
+                            unknown: 1,
+                            host: rsp.upload_ip.clone(),
+                        }),
+                        port: rsp.upload_port,
+                    }],
+                }),
+            }),
+            unknown200: 1,
+        };
+        upload_exciting(client, &data, &md5, ext.encode_to_vec()).await?;
+    }
+    let feed = D6d9ReqBody {
+        feeds_info_req: Some(FeedsReqBody {
+            group_code,
+            app_id: GROUP_FILE_APP_ID,
+            feeds_info_list: vec![GroupFileFeedsInfo {
+                bus_id: rsp.bus_id,
+                file_id: rsp.file_id.clone(),
+                msg_random: rand::random::<u32>() as i32,
+                feed_flag: 1,
+            }],
+        }),
+    };
+    send_oidb(client, FILE_FEED_CMD, 1753, 4, feed.encode_to_vec()).await?;
+    Ok((rsp.file_id, rsp.bus_id as i64))
+}
+
+async fn send_oidb(
+    client: &Client,
+    command_name: &str,
+    command: i32,
+    service_type: i32,
+    body: Vec<u8>,
+) -> RCResult<Vec<u8>> {
+    let pkg = OidbSsoPkg {
+        command,
+        service_type,
+        bodybuffer: body,
+        ..Default::default()
+    };
+    let packet = client
+        .engine
+        .read()
+        .await
+        .uni_packet(command_name, pkg.encode_to_vec().into());
+    let pkg = OidbSsoPkg::decode(client.send_and_wait(packet).await?.body)?;
+    if pkg.result != 0 {
+        return Err(RCError::Other(format!(
+            "{} failed: {} {}",
+            command_name, pkg.result, pkg.error_msg
+        )));
+    }
+    Ok(pkg.bodybuffer)
+}
+
+// highway http 通道，每块 head 为 ReqDataHighwayHead，0x28 + head 长度 + body 长度 + head + body + 0x29
+async fn upload_exciting(client: &Client, data: &[u8], md5: &[u8], ext: Vec<u8>) -> RCResult<()> {
+    let addr = client
+        .highway_addrs
+        .read()
+        .await
+        .first()
+        .cloned()
+        .ok_or(RCError::Other("no highway addr".into()))?;
+    let (app_id, sig_session) = {
+        let session = client.highway_session.read().await;
+        (session.app_id, session.sig_session.to_vec())
+    };
+    let uin = client.uin().await;
+    let url = format!(
+        "http://{}/cgi-bin/httpconn?htcmd=0x6FF0087&uin={}",
+        SocketAddr::from(addr),
+        uin
+    );
+    let http = reqwest::Client::new();
+    for (i, chunk) in data.chunks(EXCITING_CHUNK_SIZE).enumerate() {
+        let head = ReqDataHighwayHead {
+            msg_basehead: Some(DataHighwayHead {
+                version: 1,
+                uin: uin.to_string(),
+                command: "PicUp.DataUp".into(),
+                seq: i as i32 + 1,
+                appid: app_id as i32,
+                dataflag: 0,
+                command_id: EXCITING_COMMAND_ID,
+                locale_id: 0,
+            }),
+            msg_seghead: Some(SegHead {
+                filesize: data.len() as i64,
+                dataoffset: (i * EXCITING_CHUNK_SIZE) as i64,
+                datalength: chunk.len() as i32,
+                serviceticket: sig_session.clone(),
+                md5: md5::compute(chunk).to_vec(),
+                file_md5: md5.to_vec(),
+            }),
+            req_extendinfo: ext.clone(),
+        }
+        .encode_to_vec();
+        let mut resp = http
+            .post(&url)
+            .body(highway_frame(&head, chunk))
+            .send()
+            .await?
+            .bytes()
+            .await?;
+        if resp.len() < 9 {
+            return Err(RCError::Other("invalid highway resp".into()));
+        }
+        resp.advance(1);
+        let head_len = resp.get_u32() as usize;
+        resp.advance(4);
+        let head = RspDataHighwayHead::decode(resp.get(..head_len).unwrap_or_default())?;
+        if head.error_code != 0 {
+            return Err(RCError::Other(format!(
+                "highway upload failed: {}",
+                head.error_code
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn highway_frame(head: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut frame = BytesMut::with_capacity(head.len() + body.len() + 10);
+    frame.put_u8(0x28);
+    frame.put_u32(head.len() as u32);
+    frame.put_u32(body.len() as u32);
+    frame.put_slice(head);
+    frame.put_slice(body);
+    frame.put_u8(0x29);
+    frame.to_vec()
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OidbSsoPkg {
+    #[prost(int32, tag = "1")]
+    pub command: i32,
+    #[prost(int32, tag = "2")]
+    pub service_type: i32,
+    #[prost(int32, tag = "3")]
+    pub result: i32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub bodybuffer: Vec<u8>,
+    #[prost(string, tag = "5")]
+    pub error_msg: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct D6d6ReqBody {
+    #[prost(message, optional, tag = "1")]
+    pub upload_file_req: Option<UploadFileReqBody>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct UploadFileReqBody {
+    #[prost(int64, tag = "1")]
+    pub group_code: i64,
+    #[prost(int32, tag = "2")]
+    pub app_id: i32,
+    #[prost(int32, tag = "3")]
+    pub bus_id: i32,
+    #[prost(int32, tag = "4")]
+    pub entrance: i32,
+    #[prost(string, tag = "5")]
+    pub parent_folder_id: String,
+    #[prost(string, tag = "6")]
+    pub file_name: String,
+    #[prost(string, tag = "7")]
+    pub local_path: String,
+    #[prost(int64, tag = "8")]
+    pub file_size: i64,
+    #[prost(bytes = "vec", tag = "9")]
+    pub sha: Vec<u8>,
+    #[prost(bytes = "vec", tag = "11")]
+    pub md5: Vec<u8>,
+    #[prost(bool, tag = "15")]
+    pub support_multi_upload: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct D6d6RspBody {
+    #[prost(message, optional, tag = "1")]
+    pub upload_file_rsp: Option<UploadFileRspBody>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct UploadFileRspBody {
+    #[prost(int32, tag = "1")]
+    pub ret_code: i32,
+    #[prost(string, tag = "2")]
+    pub ret_msg: String,
+    #[prost(string, tag = "3")]
+    pub client_wording: String,
+    #[prost(string, tag = "4")]
+    pub upload_ip: String,
+    #[prost(int32, tag = "6")]
+    pub bus_id: i32,
+    #[prost(string, tag = "7")]
+    pub file_id: String,
+    #[prost(bytes = "vec", tag = "8")]
+    pub check_key: Vec<u8>,
+    #[prost(bool, tag = "10")]
+    pub bool_file_exist: bool,
+    #[prost(int32, tag = "14")]
+    pub upload_port: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct D6d9ReqBody {
+    #[prost(message, optional, tag = "5")]
+    pub feeds_info_req: Option<FeedsReqBody>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FeedsReqBody {
+    #[prost(int64, tag = "1")]
+    pub group_code: i64,
+    #[prost(int32, tag = "2")]
+    pub app_id: i32,
+    #[prost(message, repeated, tag = "3")]
+    pub feeds_info_list: Vec<GroupFileFeedsInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GroupFileFeedsInfo {
+    #[prost(int32, tag = "1")]
+    pub bus_id: i32,
+    #[prost(string, tag = "2")]
+    pub file_id: String,
+    #[prost(int32, tag = "3")]
+    pub msg_random: i32,
+    #[prost(int32, tag = "5")]
+    pub feed_flag: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GroupFileUploadExt {
+    #[prost(int32, tag = "1")]
+    pub unknown1: i32,
+    #[prost(int32, tag = "2")]
+    pub unknown2: i32,
+    #[prost(message, optional, tag = "100")]
+    pub entry: Option<GroupFileUploadEntry>,
+    #[prost(int32, tag = "200")]
+    pub unknown200: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GroupFileUploadEntry {
+    #[prost(message, optional, tag = "100")]
+    pub busi_buff: Option<ExcitingBusiInfo>,
+    #[prost(message, optional, tag = "200")]
+    pub file_entry: Option<ExcitingFileEntry>,
+    #[prost(message, optional, tag = "300")]
+    pub client_info: Option<ExcitingClientInfo>,
+    #[prost(message, optional, tag = "400")]
+    pub file_name_info: Option<ExcitingFileNameInfo>,
+    #[prost(message, optional, tag = "500")]
+    pub host: Option<ExcitingHostConfig>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingBusiInfo {
+    #[prost(int32, tag = "1")]
+    pub bus_id: i32,
+    #[prost(int64, tag = "100")]
+    pub sender_uin: i64,
+    #[prost(int64, tag = "200")]
+    pub receiver_uin: i64,
+    #[prost(int64, tag = "400")]
+    pub group_code: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingFileEntry {
+    #[prost(int64, tag = "100")]
+    pub file_size: i64,
+    #[prost(bytes = "vec", tag = "200")]
+    pub md5: Vec<u8>,
+    #[prost(bytes = "vec", tag = "300")]
+    pub sha1: Vec<u8>,
+    #[prost(bytes = "vec", tag = "600")]
+    pub file_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "700")]
+    pub upload_key: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingClientInfo {
+    #[prost(int32, tag = "100")]
+    pub client_type: i32,
+    #[prost(string, tag = "200")]
+    pub app_id: String,
+    #[prost(int32, tag = "300")]
+    pub terminal_type: i32,
+    #[prost(string, tag = "400")]
+    pub client_ver: String,
+    #[prost(int32, tag = "600")]
+    pub unknown: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingFileNameInfo {
+    #[prost(string, tag = "100")]
+    pub file_name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingHostConfig {
+    #[prost(message, repeated, tag = "200")]
+    pub hosts: Vec<ExcitingHostInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingHostInfo {
+    #[prost(message, optional, tag = "1")]
+    pub url: Option<ExcitingUrlInfo>,
+    #[prost(int32, tag = "2")]
+    pub port: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExcitingUrlInfo {
+    #[prost(int32, tag = "1")]
+    pub unknown: i32,
+    #[prost(string, tag = "2")]
+    pub host: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ReqDataHighwayHead {
+    #[prost(message, optional, tag = "1")]
+    pub msg_basehead: Option<DataHighwayHead>,
+    #[prost(message, optional, tag = "2")]
+    pub msg_seghead: Option<SegHead>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub req_extendinfo: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RspDataHighwayHead {
+    #[prost(int32, tag = "3")]
+    pub error_code: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DataHighwayHead {
+    #[prost(int32, tag = "1")]
+    pub version: i32,
+    #[prost(string, tag = "2")]
+    pub uin: String,
+    #[prost(string, tag = "3")]
+    pub command: String,
+    #[prost(int32, tag = "4")]
+    pub seq: i32,
+    #[prost(int32, tag = "6")]
+    pub appid: i32,
+    #[prost(int32, tag = "7")]
+    pub dataflag: i32,
+    #[prost(int32, tag = "8")]
+    pub command_id: i32,
+    #[prost(int32, tag = "10")]
+    pub locale_id: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SegHead {
+    #[prost(int64, tag = "2")]
+    pub filesize: i64,
+    #[prost(int64, tag = "3")]
+    pub dataoffset: i64,
+    #[prost(int32, tag = "4")]
+    pub datalength: i32,
+    #[prost(bytes = "vec", tag = "6")]
+    pub serviceticket: Vec<u8>,
+    #[prost(bytes = "vec", tag = "8")]
+    pub md5: Vec<u8>,
+    #[prost(bytes = "vec", tag = "9")]
+    pub file_md5: Vec<u8>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct FileList {
+    pub file_list: Vec<FileItem>,
+}
+
+// type 1 为文件，2 为文件夹
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct FileItem {
+    #[serde(rename = "type")]
+    pub item_type: i32,
+    pub id: String,
+    pub name: String,
+    pub size: i64,
+    pub bus_id: i64,
+    pub create_time: i64,
+    pub owner_uin: i64,
+    pub owner_name: String,
+    pub download_times: i32,
+}
+
+impl FileList {
+    pub fn into_resp(
+        self,
+    ) -> (
+        Vec<get_group_files_resp::GroupFile>,
+        Vec<get_group_files_resp::GroupFolder>,
+    ) {
+        let mut files = Vec::new();
+        let mut folders = Vec::new();
+        for item in self.file_list {
+            match item.item_type {
+                1 => files.push(get_group_files_resp::GroupFile {
+                    id: item.id,
+                    name: item.name,
+                    size: item.size,
+                    busid: item.bus_id,
+                    upload_time: item.create_time,
+                    uploader: item.owner_uin,
+                    uploader_name: item.owner_name,
+                    download_times: item.download_times,
+                }),
+                2 => folders.push(get_group_files_resp::GroupFolder {
+                    id: item.id,
+                    name: item.name,
+                    create_time: item.create_time,
+                    creator: item.owner_uin,
+                    creator_name: item.owner_name,
+                }),
+                _ => {}
+            }
+        }
+        (files, folders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_group_file() {
+        let obj = ObjMsg {
+            msg_type: Some(6),
+            msg_content_info: vec![MsgContentInfo {
+                content_info_id: None,
+                msg_file: Some(MsgFile {
+                    bus_id: Some(102),
+                    file_path: Some(b"/abc".to_vec()),
+                    file_size: Some(1024),
+                    file_name: Some("a.txt".into()),
+                }),
+            }],
+        }
+        .encode_to_vec();
+        let mut value = vec![1];
+        value.extend((obj.len() as u16).to_be_bytes());
+        value.extend(obj);
+        let file = decode_group_file(&value).unwrap();
+        assert_eq!(file.bus_id, Some(102));
+        assert_eq!(file.file_name.as_deref(), Some("a.txt"));
+        assert!(decode_group_file(&value[..5]).is_none());
+    }
+
+    #[test]
+    fn test_file_name_from_uri() {
+        assert_eq!(
+            file_name_from_uri("https://a.com/dir/a.txt?x=1").as_deref(),
+            Some("a.txt")
+        );
+        assert_eq!(
+            file_name_from_uri("file:///tmp/b.zip").as_deref(),
+            Some("b.zip")
+        );
+        assert_eq!(
+            file_name_from_uri("C:\\data\\c.pdf").as_deref(),
+            Some("c.pdf")
+        );
+        assert_eq!(file_name_from_uri("base64://AAAA"), None);
+        assert_eq!(file_name_from_uri("https://a.com/"), None);
+    }
+
+    #[test]
+    fn test_highway_frame() {
+        let frame = highway_frame(b"head", b"data!");
+        assert_eq!(frame[0], 0x28);
+        assert_eq!(&frame[1..9], &[0, 0, 0, 4, 0, 0, 0, 5]);
+        assert_eq!(&frame[9..18], b"headdata!");
+        assert_eq!(frame[18], 0x29);
+    }
+
+    #[test]
+    fn test_file_list() {
+        let list: FileList = serde_json::from_str(
+            r#"{"ec":0,"file_list":[{"type":1,"id":"/abc","name":"a.txt","size":10,"bus_id":102,"owner_uin":1},{"type":2,"id":"/f","name":"dir"}]}"#,
+        )
+        .unwrap();
+        let (files, folders) = list.into_resp();
+        assert_eq!(files[0].busid, 102);
+        assert_eq!(folders[0].name, "dir");
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::{RCError, RCResult};
+use crate::idl::pbbot::{get_group_honor_info_resp, GetGroupHonorInfoResp};
+
+pub const HONOR_URL: &str = "https://qun.qq.com/interactive/honorlist";
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct HonorPage {
+    #[serde(rename = "currentTalkative")]
+    pub current_talkative: Option<CurrentTalkative>,
+    #[serde(rename = "talkativeList")]
+    pub talkative_list: Vec<HonorMember>,
+    #[serde(rename = "actorList")]
+    pub actor_list: Vec<HonorMember>,
+    #[serde(rename = "legendList")]
+    pub legend_list: Vec<HonorMember>,
+    #[serde(rename = "strongnewbieList")]
+    pub strong_newbie_list: Vec<HonorMember>,
+    #[serde(rename = "emotionList")]
+    pub emotion_list: Vec<HonorMember>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct CurrentTalkative {
+    pub uin: i64,
+    pub nick: String,
+    pub avatar: String,
+    pub day_count: i32,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct HonorMember {
+    pub uin: i64,
+    pub name: String,
+    pub avatar: String,
+    pub desc: String,
+}
+
+impl From<HonorMember> for get_group_honor_info_resp::Honor {
+    fn from(m: HonorMember) -> Self {
+        Self {
+            user_id: m.uin,
+            nickname: m.name,
+            avatar: m.avatar,
+            description: m.desc,
+        }
+    }
+}
+
+// 网页中 window.__INITIAL_STATE__ 为荣誉信息
+pub fn parse_honor_page(html: &str) -> RCResult<HonorPage> {
+    let start = html
+        .find("window.__INITIAL_STATE__")
+        .and_then(|i| html[i..].find('{').map(|j| i + j))
+        .ok_or_else(|| RCError::Other("honor page without __INITIAL_STATE__".into()))?;
+    let mut de = serde_json::Deserializer::from_str(&html[start..]);
+    HonorPage::deserialize(&mut de)
+        .map_err(|e| RCError::Other(format!("invalid honor page: {}", e)))
+}
+
+pub async fn fetch_honor_page(
+    base_url: &str,
+    cookies: &str,
+    group_id: i64,
+    honor_type: i32,
+) -> RCResult<HonorPage> {
+    let html = reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()?
+        .get(base_url)
+        .query(&[("gc", group_id), ("type", honor_type as i64)])
+        .header("Cookie", cookies)
+        .send()
+        .await?
+        .text()
+        .await?;
+    parse_honor_page(&html)
+}
+
+// honor_type: talkative, performer, legend, strong_newbie, emotion, all
+pub async fn get_group_honor_info(
+    base_url: &str,
+    cookies: &str,
+    group_id: i64,
+    honor_type: &str,
+) -> RCResult<GetGroupHonorInfoResp> {
+    let all = honor_type == "all";
+    let mut resp = GetGroupHonorInfoResp {
+        group_id,
+        ..Default::default()
+    };
+    if all || honor_type == "talkative" {
+        let page = fetch_honor_page(base_url, cookies, group_id, 1).await?;
+        resp.current_talkative =
+            page.current_talkative
+                .map(|c| get_group_honor_info_resp::CurrentTalkative {
+                    user_id: c.uin,
+                    nickname: c.nick,
+                    avatar: c.avatar,
+                    day_count: c.day_count,
+                });
+        resp.talkative = page.talkative_list.into_iter().map(Into::into).collect();
+    }
+    if all || honor_type == "performer" {
+        let page = fetch_honor_page(base_url, cookies, group_id, 2).await?;
+        resp.performer = page.actor_list.into_iter().map(Into::into).collect();
+    }
+    if all || honor_type == "legend" {
+        let page = fetch_honor_page(base_url, cookies, group_id, 3).await?;
+        resp.legend = page.legend_list.into_iter().map(Into::into).collect();
+    }
+    if all || honor_type == "strong_newbie" {
+        let page = fetch_honor_page(base_url, cookies, group_id, 5).await?;
+        resp.strong_newbie = page
+            .strong_newbie_list
+            .into_iter()
+            .map(Into::into)
+            .collect();
+    }
+    if all || honor_type == "emotion" {
+        let page = fetch_honor_page(base_url, cookies, group_id, 6).await?;
+        resp.emotion = page.emotion_list.into_iter().map(Into::into).collect();
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::extract::Query;
+    use axum::routing::get;
+    use axum::Router;
+
+    use super::*;
+
+    const TALKATIVE_PAGE: &str = r#"<html><script>window.__INITIAL_STATE__={"gc":"123","type":1,"currentTalkative":{"uin":111,"day_count":3,"avatar":"a1","nick":"foo"},"talkativeList":[{"uin":111,"avatar":"a1","name":"foo","desc":"连续3天"}]};(function(){})();</script></html>"#;
+    const EMOTION_PAGE: &str = r#"<script>window.__INITIAL_STATE__ = {"gc":"123","type":6,"emotionList":[{"uin":222,"avatar":"a2","name":"bar","desc":"快乐源泉"}]}</script>"#;
+
+    #[test]
+    fn test_parse_honor_page() {
+        let page = parse_honor_page(TALKATIVE_PAGE).unwrap();
+        let current = page.current_talkative.unwrap();
+        assert_eq!(current.uin, 111);
+        assert_eq!(current.day_count, 3);
+        assert_eq!(page.talkative_list[0].desc, "连续3天");
+        assert!(parse_honor_page("<html></html>").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_group_honor_info() {
+        let app = Router::new().route(
+            "/honorlist",
+            get(|Query(q): Query<HashMap<String, String>>| async move {
+                assert_eq!(q.get("gc").map(String::as_str), Some("123"));
+                match q.get("type").map(String::as_str) {
+                    Some("1") => TALKATIVE_PAGE,
+                    Some("6") => EMOTION_PAGE,
+                    _ => "<html></html>",
+                }
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/honorlist", listener.local_addr().unwrap());
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        let resp = get_group_honor_info(&base_url, "", 123, "talkative")
+            .await
+            .unwrap();
+        assert_eq!(resp.current_talkative.unwrap().nickname, "foo");
+        assert_eq!(resp.talkative[0].user_id, 111);
+        assert!(resp.emotion.is_empty());
+
+        let resp = get_group_honor_info(&base_url, "", 123, "emotion")
+            .await
+            .unwrap();
+        assert_eq!(resp.emotion[0].description, "快乐源泉");
+
+        assert!(get_group_honor_info(&base_url, "", 123, "legend")
+            .await
+            .is_err());
+    }
+}
@@ -1 +1,5 @@
+pub mod audio;
+pub mod group_file;
+pub mod honor;
+pub mod private_file;
 pub mod uri_reader;
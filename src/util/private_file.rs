@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use tokio::io::AsyncWriteExt;
+
+use crate::error::RCResult;
+
+// token、设备信息等只允许当前用户读写
+pub async fn write_private_file(path: impl AsRef<Path>, data: &[u8]) -> RCResult<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    // 已经存在的文件 open 时不会修改权限
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+    file.write_all(data).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_write_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("pbrq_private_{}", std::process::id()));
+        tokio::fs::write(&path, b"old").await.unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_private_file(&path, b"new").await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"new");
+        std::fs::remove_file(&path).unwrap();
+    }
+}